@@ -0,0 +1,137 @@
+//! Alternative frequency following
+//!
+//! TEA5767 has no RDS, so the list of frequencies carrying the same programme is supplied by
+//! the application. The follower is called periodically with a time stamp from any millisecond
+//! clock. Each check measures one alternative frequency in a short mute-retune-measure cycle
+//! and switches to it when it is stronger than the tuned one by the hysteresis margin.
+
+use super::defs::*;
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::blocking::i2c;
+
+/// Alternative frequency follower configuration
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AfConfig {
+    /// ADC level margin by which an alternative has to be stronger to switch, 0 - 15
+    pub hysteresis: u8,
+    /// time between two measurements, ms
+    pub interval_ms: u32,
+    /// time the PLL settles on an alternative before it is measured, ms
+    pub settle_ms: u16,
+}
+
+impl Default for AfConfig {
+    fn default() -> Self {
+        AfConfig {
+            hysteresis: 2,
+            interval_ms: 5000,
+            settle_ms: 10,
+        }
+    }
+}
+
+/// Events reported by the follower, frequencies in MHz
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AfEvent {
+    /// Alternative measured, tuned frequency kept
+    Measured { frequency: f32, level: u8 },
+    /// Switched to a stronger alternative
+    Switched { from: f32, to: f32 },
+}
+
+/// Alternative frequency follower for one programme
+#[derive(Debug)]
+pub struct AfFollower<'a> {
+    frequencies: &'a [f32],
+    config: AfConfig,
+    last_check: Option<u32>,
+    next: usize,
+}
+
+impl<'a> AfFollower<'a> {
+    /// Create new follower for equivalent frequencies of a programme, MHz
+    pub fn new(frequencies: &'a [f32], config: AfConfig) -> Self {
+        AfFollower {
+            frequencies,
+            config,
+            last_check: None,
+            next: 0,
+        }
+    }
+
+    /// Get follower configuration
+    pub fn config(&self) -> AfConfig {
+        self.config
+    }
+
+    /// Get equivalent frequencies, MHz
+    pub fn frequencies(&self) -> &'a [f32] {
+        self.frequencies
+    }
+
+    /// Replace equivalent frequencies, e.g. after programme change
+    pub fn set_frequencies(&mut self, frequencies: &'a [f32]) {
+        self.frequencies = frequencies;
+        self.next = 0;
+    }
+
+    /// Measure next alternative once the interval has elapsed, nothing is done while the tuned
+    /// frequency is not one of the programme frequencies
+    pub fn check<I2C, E, MODE, D>(&mut self, tuner: &mut TEA5767<I2C, MODE>, now_ms: u32,
+                                  delay: &mut D) -> Result<Option<AfEvent>, E>
+    where
+        I2C: i2c::Write<Error = E> + i2c::Read<Error = E>,
+        MODE: Tunable,
+        D: DelayMs<u16>,
+    {
+        if let Some(last_check) = self.last_check {
+            if now_ms.wrapping_sub(last_check) < self.config.interval_ms {
+                return Ok(None);
+            }
+        }
+
+        let tuned = tuner.frequency;
+        if !self.frequencies.iter().any(|&frequency| same_channel(frequency, tuned)) {
+            return Ok(None);
+        }
+        let alternative = match self.next_alternative(tuned) {
+            Some(frequency) => frequency,
+            None => return Ok(None),
+        };
+        self.last_check = Some(now_ms);
+
+        let level = tuner.read_registers()?.level;
+        let registers = tuner.measure_channel(alternative, self.config.settle_ms, delay)?;
+
+        let locked = registers.if_counter >= IF_COUNTER_TUNED.0
+            && registers.if_counter <= IF_COUNTER_TUNED.1;
+        if locked && registers.level >= level.saturating_add(self.config.hysteresis) {
+            tuner.set_frequency(alternative)?;
+            Ok(Some(AfEvent::Switched { from: tuned, to: alternative }))
+        } else {
+            Ok(Some(AfEvent::Measured { frequency: alternative, level: registers.level }))
+        }
+    }
+
+    // Alternatives are measured in turn, the tuned frequency is skipped
+    fn next_alternative(&mut self, tuned: f32) -> Option<f32> {
+        for _ in 0..self.frequencies.len() {
+            let frequency = self.frequencies[self.next % self.frequencies.len()];
+            self.next = (self.next + 1) % self.frequencies.len();
+            if !same_channel(frequency, tuned) {
+                return Some(frequency);
+            }
+        }
+        None
+    }
+}
+
+// frequencies within half of the narrowest channel spacing
+fn same_channel(a: f32, b: f32) -> bool {
+    (a - b).abs() < 0.025
+}
@@ -0,0 +1,276 @@
+//! Command line tool controlling TEA5767 over Linux i2c-dev
+//!
+//! The tool attaches to the running tuner, so a command does not retune the radio unless it
+//! asks to. Write registers cannot be read back, band, de-emphasis and channel grid are taken
+//! from `--region` and the reference clock from `--clock`, everything else is written with
+//! driver defaults by the first command writing the device. With `--sim` the commands run
+//! against the driver's simulated device.
+
+use embedded_hal::blocking::i2c::{Read, Write};
+use linux_embedded_hal::I2cdev;
+use std::{env, fmt, process, thread, time::Duration};
+use tea5767::command::{Command, Reply};
+use tea5767::defs::*;
+use tea5767::regs::{ReadRegisters, DEVICE_ADDRESS};
+use tea5767::sim::{SimulatedTuner, Station};
+
+const USAGE: &str = "\
+usage: tea5767-cli [--device <path>] [--region <region>] [--clock <clock>] [--sim] [--json]
+                   <command>
+
+commands:
+    tune <MHz>
+    seek up|down [low|mid|high]
+    scan [low|mid|high]
+    status [--watch] [--interval <ms>] [--count <n>]
+    mute [l|r|off]
+    standby on|off
+    dump-registers
+
+options:
+    --device <path>  i2c-dev device, default /dev/i2c-1
+    --region <name>  europe|usa|japan|korea|australia, default full Europe/US band
+    --clock <clock>  reference clock 32k|6.5m|13m, default 32k
+    --sim            use simulated device instead of i2c-dev
+    --json           print JSON instead of text";
+
+// Stations received by simulated device
+const SIM_STATIONS: [Station; 4] = [
+    Station { frequency: 89.9, level: 6, stereo: false },
+    Station { frequency: 95.1, level: 9, stereo: true },
+    Station { frequency: 101.3, level: 13, stereo: true },
+    Station { frequency: 104.7, level: 11, stereo: true },
+];
+
+#[derive(Debug)]
+struct Options {
+    device: String,
+    region: Option<Region>,
+    clock: CrystalFrequency,
+    sim: bool,
+    json: bool,
+    watch: bool,
+    interval: u64,
+    count: Option<u64>,
+    command: Vec<String>,
+}
+
+fn main() {
+    let options = match parse_options(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("error: {}\n\n{}", error, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let result = if options.sim {
+        let mut sim = SimulatedTuner::new(&SIM_STATIONS);
+        sim.set_clock(options.clock);
+        run(sim, &options)
+    } else {
+        I2cdev::new(&options.device)
+            .map_err(|error| format!("{}: {}", options.device, error))
+            .and_then(|i2c| run(i2c, &options))
+    };
+
+    if let Err(error) = result {
+        eprintln!("error: {}", error);
+        process::exit(1);
+    }
+}
+
+fn parse_options<A: Iterator<Item = String>>(mut args: A) -> Result<Options, String> {
+    let mut options = Options {
+        device: String::from("/dev/i2c-1"),
+        region: None,
+        clock: CrystalFrequency::Clk32_768Khz,
+        sim: false,
+        json: false,
+        watch: false,
+        interval: 1000,
+        count: None,
+        command: Vec::new(),
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--device" => options.device = args.next().ok_or("--device needs a path")?,
+            "--region" => options.region = Some(parse_region(args.next())?),
+            "--clock" => options.clock = parse_clock(args.next())?,
+            "--sim" => options.sim = true,
+            "--json" => options.json = true,
+            "--watch" => options.watch = true,
+            "--interval" => options.interval = parse_number(args.next(), "--interval")?,
+            "--count" => options.count = Some(parse_number(args.next(), "--count")?),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => options.command.push(arg),
+        }
+    }
+
+    if options.command.is_empty() {
+        return Err(String::from("missing command"));
+    }
+    Ok(options)
+}
+
+fn parse_region(arg: Option<String>) -> Result<Region, String> {
+    match arg.as_deref() {
+        Some("europe") => Ok(Region::Europe),
+        Some("usa") => Ok(Region::USA),
+        Some("japan") => Ok(Region::Japan),
+        Some("korea") => Ok(Region::Korea),
+        Some("australia") => Ok(Region::Australia),
+        _ => Err(String::from("--region needs europe, usa, japan, korea or australia")),
+    }
+}
+
+fn parse_clock(arg: Option<String>) -> Result<CrystalFrequency, String> {
+    match arg.as_deref() {
+        Some("32k") => Ok(CrystalFrequency::Clk32_768Khz),
+        Some("6.5m") => Ok(CrystalFrequency::Clk6_5MHz),
+        Some("13m") => Ok(CrystalFrequency::Clk13Mhz),
+        _ => Err(String::from("--clock needs 32k, 6.5m or 13m")),
+    }
+}
+
+// Configuration of the running tuner as far as given by options
+fn tuner_config(options: &Options) -> TunerConfig {
+    let mut config = TunerConfig {
+        crystal_frequency: options.clock,
+        ..Default::default()
+    };
+    if let Some(region) = options.region {
+        let profile = region.profile();
+        config.band_limits = profile.band_limits;
+        config.frequency_range = profile.frequency_range;
+        config.deemphasis_time = profile.deemphasis_time;
+        config.channel_spacing = profile.channel_spacing;
+        config.seek_step = profile.seek_step;
+    }
+    config
+}
+
+fn parse_number(arg: Option<String>, option: &str) -> Result<u64, String> {
+    arg.and_then(|value| value.parse().ok())
+        .ok_or(format!("{} needs a number", option))
+}
+
+fn run<I2C, E>(mut i2c: I2C, options: &Options) -> Result<(), String>
+where
+    I2C: Write<Error = E> + Read<Error = E>,
+    E: fmt::Debug,
+{
+    let args: Vec<&str> = options.command.iter().map(String::as_str).collect();
+
+    if args == ["dump-registers"] {
+        let mut registers = [0_u8; 5];
+        i2c.read(DEVICE_ADDRESS, &mut registers).map_err(device_error)?;
+        print_registers(&registers, options.json);
+        return Ok(());
+    }
+
+    let mut tuner = TEA5767::attach(i2c, tuner_config(options)).map_err(device_error)?;
+
+    let command = match args.as_slice() {
+        ["status"] if options.watch => {
+            let mut count = 0;
+            while options.count.map_or(true, |limit| count < limit) {
+                if count > 0 {
+                    thread::sleep(Duration::from_millis(options.interval));
+                }
+                let status = tuner.get_status().map_err(device_error)?;
+                print_reply(&Reply::Status(status), options.json);
+                count += 1;
+            }
+            return Ok(());
+        }
+        ["mute", "off"] => Command::Unmute(MuteChannel::Both),
+        ["status", ..] | ["tune", ..] | ["seek", ..] | ["scan", ..] | ["mute", ..]
+        | ["standby", ..] => {
+            Command::parse(&args.join(" ")).map_err(|error| format!("{:?}", error))?
+        }
+        _ => return Err(format!("unknown command: {}", args.join(" "))),
+    };
+
+    let reply = command.execute(&mut tuner).map_err(device_error)?;
+    print_reply(&reply, options.json);
+    Ok(())
+}
+
+fn device_error<E: fmt::Debug>(error: E) -> String {
+    format!("device access failed: {:?}", error)
+}
+
+fn print_reply(reply: &Reply, json: bool) {
+    match (reply, json) {
+        (Reply::Tuned(frequency), false) => println!("tuned {:.1} MHz", frequency),
+        (Reply::Tuned(frequency), true) => println!("{{\"frequency\":{:.1}}}", frequency),
+        (Reply::Seek(status, frequency), false) => match status {
+            SearchStatus::Success => println!("found {:.1} MHz", frequency),
+            SearchStatus::Failure => println!("band limit reached at {:.1} MHz", frequency),
+        },
+        (Reply::Seek(status, frequency), true) => println!(
+            "{{\"found\":{},\"frequency\":{:.1}}}",
+            *status == SearchStatus::Success, frequency
+        ),
+        (Reply::Scan { stations, count }, false) => {
+            for station in &stations[..*count] {
+                println!("{:.1} MHz", station);
+            }
+        }
+        (Reply::Scan { stations, count }, true) => {
+            let stations: Vec<String> = stations[..*count]
+                .iter()
+                .map(|station| format!("{:.1}", station))
+                .collect();
+            println!("{{\"stations\":[{}]}}", stations.join(","));
+        }
+        (Reply::Status(status), false) => println!(
+            "{:.1} MHz, {}, level {}",
+            status.frequency,
+            if status.sound_mode == SoundMode::Stereo { "stereo" } else { "mono" },
+            status.signal_level
+        ),
+        (Reply::Status(status), true) => println!(
+            "{{\"frequency\":{:.1},\"stereo\":{},\"level\":{},\"ready\":{},\"band_limit\":{}}}",
+            status.frequency,
+            status.sound_mode == SoundMode::Stereo,
+            status.signal_level,
+            status.ready,
+            status.band_limit
+        ),
+        (Reply::Ok, false) => println!("ok"),
+        (Reply::Ok, true) => println!("{{}}"),
+        (reply, _) => println!("{}", reply),
+    }
+}
+
+fn print_registers(bytes: &[u8; 5], json: bool) {
+    let registers = ReadRegisters::from_bytes(*bytes);
+
+    if json {
+        println!(
+            "{{\"bytes\":[{},{},{},{},{}],\"ready\":{},\"band_limit\":{},\"pll\":{},\
+             \"stereo\":{},\"if_counter\":{},\"level\":{},\"chip_id\":{}}}",
+            bytes[0], bytes[1], bytes[2], bytes[3], bytes[4],
+            registers.ready, registers.band_limit, registers.pll, registers.stereo,
+            registers.if_counter, registers.level, registers.chip_id
+        );
+    } else {
+        println!(
+            "{:02X} {:02X} {:02X} {:02X} {:02X}",
+            bytes[0], bytes[1], bytes[2], bytes[3], bytes[4]
+        );
+        println!("ready flag       {}", registers.ready as u8);
+        println!("band limit flag  {}", registers.band_limit as u8);
+        println!("PLL              {}", registers.pll);
+        println!("stereo           {}", registers.stereo as u8);
+        println!("IF counter       0x{:02X}", registers.if_counter);
+        println!("ADC level        {}", registers.level);
+        println!("chip ID          {}", registers.chip_id);
+    }
+}
@@ -0,0 +1,125 @@
+//! Bus sharing for TEA5767 driver
+//!
+//! The driver is based on [`embedded-hal`] 0.2 I2C traits and owns its `I2C`. Devices in this
+//! module implement those traits on top of a shared bus, so the tuner can share it with other
+//! devices. With a detached driver the bus is borrowed only for the duration of a call.
+//!
+//! [`embedded-hal`]: https://github.com/rust-embedded/embedded-hal
+
+use super::defs::*;
+use core::cell::RefCell;
+use embedded_hal::blocking::i2c;
+
+/// Bus placeholder of a driver created with `detach`
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub struct Detached;
+
+/// I2C borrowed for the duration of a call, created by `with_bus`
+#[derive(Debug)]
+pub struct BorrowedDevice<'a, I2C> {
+    bus: &'a mut I2C,
+}
+
+/// I2C shared through RefCell, for sharing within a single thread or interrupt priority
+#[derive(Debug)]
+pub struct RefCellDevice<'a, I2C> {
+    bus: &'a RefCell<I2C>,
+}
+
+/// I2C shared through critical section mutex, for sharing between interrupts and threads
+#[cfg(feature = "critical-section")]
+#[derive(Debug)]
+pub struct CriticalSectionDevice<'a, I2C> {
+    bus: &'a critical_section::Mutex<RefCell<I2C>>,
+}
+
+impl<'a, I2C> RefCellDevice<'a, I2C> {
+    /// Create new device on shared bus
+    pub fn new(bus: &'a RefCell<I2C>) -> Self {
+        RefCellDevice { bus }
+    }
+}
+
+#[cfg(feature = "critical-section")]
+impl<'a, I2C> CriticalSectionDevice<'a, I2C> {
+    /// Create new device on shared bus
+    pub fn new(bus: &'a critical_section::Mutex<RefCell<I2C>>) -> Self {
+        CriticalSectionDevice { bus }
+    }
+}
+
+impl<I2C, MODE> TEA5767<I2C, MODE> {
+    /// Give the bus back and keep driver configuration, bus is then lent per call with `with_bus`
+    pub fn detach(self) -> (I2C, TEA5767<Detached, MODE>) {
+        self.replace_bus(Detached)
+    }
+}
+
+impl<MODE> TEA5767<Detached, MODE> {
+    /// Call driver methods with the bus borrowed only for the duration of the closure
+    pub fn with_bus<I2C, R, F>(&mut self, bus: &mut I2C, f: F) -> R
+    where F: FnOnce(&mut TEA5767<BorrowedDevice<'_, I2C>, MODE>) -> R
+    {
+        let mut tuner = self.rebind(BorrowedDevice { bus });
+        let result = f(&mut tuner);
+        *self = tuner.rebind(Detached);
+        result
+    }
+
+    /// Give owned bus to the driver again
+    pub fn attach_bus<I2C>(self, i2c: I2C) -> TEA5767<I2C, MODE> {
+        self.replace_bus(i2c).1
+    }
+}
+
+impl<'a, I2C: i2c::Write> i2c::Write for BorrowedDevice<'a, I2C> {
+    type Error = I2C::Error;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.bus.write(address, bytes)
+    }
+}
+
+impl<'a, I2C: i2c::Read> i2c::Read for BorrowedDevice<'a, I2C> {
+    type Error = I2C::Error;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.bus.read(address, buffer)
+    }
+}
+
+impl<'a, I2C: i2c::Write> i2c::Write for RefCellDevice<'a, I2C> {
+    type Error = I2C::Error;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.bus.borrow_mut().write(address, bytes)
+    }
+}
+
+impl<'a, I2C: i2c::Read> i2c::Read for RefCellDevice<'a, I2C> {
+    type Error = I2C::Error;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.bus.borrow_mut().read(address, buffer)
+    }
+}
+
+#[cfg(feature = "critical-section")]
+impl<'a, I2C: i2c::Write> i2c::Write for CriticalSectionDevice<'a, I2C> {
+    type Error = I2C::Error;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        critical_section::with(|cs| self.bus.borrow_ref_mut(cs).write(address, bytes))
+    }
+}
+
+#[cfg(feature = "critical-section")]
+impl<'a, I2C: i2c::Read> i2c::Read for CriticalSectionDevice<'a, I2C> {
+    type Error = I2C::Error;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        critical_section::with(|cs| self.bus.borrow_ref_mut(cs).read(address, buffer))
+    }
+}
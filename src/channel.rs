@@ -0,0 +1,224 @@
+//! Channel number addressing
+//!
+//! Channels are numbered either by FCC channel number, 201 - 300 for 88.1 - 107.9 MHz, or by
+//! index on the channel grid of the frequency range, 0 being the low end of the range. Both
+//! accept only frequencies within the range and on the channel grid.
+
+use super::defs::*;
+use super::device::band_range;
+use embedded_hal::blocking::i2c;
+#[allow(unused_imports)] // float methods are inherent with std
+use micromath::F32Ext;
+
+/// FCC FM channel numbers (first, last)
+pub const FCC_CHANNELS: (u16, u16) = (201, 300);
+
+// Frequency of FCC channel 200 and FCC channel spacing, MHz
+const FCC_BASE: f32 = 87.9;
+const FCC_SPACING: f32 = 0.2;
+
+// Largest distance of a frequency from a channel, MHz
+const GRID_TOLERANCE: f32 = 0.005;
+
+/// Channel numbering
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ChannelNumbering {
+    /// FCC channel numbers 201 - 300
+    Fcc,
+    /// Index on the channel grid from the low end of the frequency range, default
+    Grid,
+}
+
+/// Channel not in the channel plan
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum ChannelError {
+    /// Channel or frequency outside the frequency range
+    OutOfBand,
+    /// Frequency between two channels of the grid
+    OffGrid,
+}
+
+/// Error of tuning to a channel
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum TuneChannelError<E> {
+    /// Device access failed
+    Bus(E),
+    /// Channel not in the channel plan, nothing has been written
+    Channel(ChannelError),
+}
+
+/// Channel numbering of a frequency range and channel grid
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ChannelPlan {
+    numbering: ChannelNumbering,
+    frequency_range: (f32, f32),
+    channel_spacing: f32,
+}
+
+impl ChannelPlan {
+    /// Create channel plan for the whole band, None when the channel spacing is below
+    /// `MIN_CHANNEL_SPACING` or wider than the band
+    pub fn new(numbering: ChannelNumbering, band_limits: BandLimits, channel_spacing: f32)
+        -> Option<Self> {
+        let (low, high) = band_range(band_limits);
+        if !(channel_spacing >= MIN_CHANNEL_SPACING && channel_spacing <= high - low) {
+            return None;
+        }
+        Some(ChannelPlan {
+            numbering,
+            frequency_range: (low, high),
+            channel_spacing,
+        })
+    }
+
+    /// Get channel numbering
+    pub fn numbering(&self) -> ChannelNumbering {
+        self.numbering
+    }
+
+    /// Get frequency range (min, max), MHz
+    pub fn frequency_range(&self) -> (f32, f32) {
+        self.frequency_range
+    }
+
+    /// Get distance between neighbouring channels, MHz
+    pub fn channel_spacing(&self) -> f32 {
+        self.channel_spacing
+    }
+
+    /// Get frequency of channel, MHz
+    pub fn frequency(&self, channel: u16) -> Result<f32, ChannelError> {
+        let frequency = match self.numbering {
+            ChannelNumbering::Fcc => {
+                if channel < FCC_CHANNELS.0 || channel > FCC_CHANNELS.1 {
+                    return Err(ChannelError::OutOfBand);
+                }
+                FCC_BASE + (channel - 200) as f32 * FCC_SPACING
+            }
+            ChannelNumbering::Grid => self.grid_frequency(channel),
+        };
+        let frequency = (frequency * 100.0).round() / 100.0;
+        self.grid_index(frequency)?;
+        Ok(frequency)
+    }
+
+    /// Get channel of frequency
+    pub fn channel(&self, frequency: f32) -> Result<u16, ChannelError> {
+        let index = self.grid_index(frequency)?;
+        match self.numbering {
+            ChannelNumbering::Fcc => {
+                let channel = ((frequency - FCC_BASE) / FCC_SPACING).round();
+                if (FCC_BASE + channel * FCC_SPACING - frequency).abs() > GRID_TOLERANCE {
+                    return Err(ChannelError::OffGrid);
+                }
+                let channel = channel as i32 + 200;
+                if channel < FCC_CHANNELS.0 as i32 || channel > FCC_CHANNELS.1 as i32 {
+                    return Err(ChannelError::OutOfBand);
+                }
+                Ok(channel as u16)
+            }
+            ChannelNumbering::Grid => Ok(index),
+        }
+    }
+
+    /// Number of channels of the grid
+    pub fn channels(&self) -> u16 {
+        let (low, high) = self.frequency_range;
+        ((high - low) / self.channel_spacing + 0.01).floor() as u16 + 1
+    }
+
+    // Frequency of index on the channel grid, MHz
+    pub(crate) fn grid_frequency(&self, index: u16) -> f32 {
+        ((self.frequency_range.0 + index as f32 * self.channel_spacing) * 100.0).round() / 100.0
+    }
+
+    // Index of frequency on the channel grid
+    pub(crate) fn grid_index(&self, frequency: f32) -> Result<u16, ChannelError> {
+        let (low, high) = self.frequency_range;
+        if frequency < low - GRID_TOLERANCE || frequency > high + GRID_TOLERANCE {
+            return Err(ChannelError::OutOfBand);
+        }
+        let index = ((frequency - low) / self.channel_spacing).round();
+        if (low + index * self.channel_spacing - frequency).abs() > GRID_TOLERANCE {
+            return Err(ChannelError::OffGrid);
+        }
+        Ok(index as u16)
+    }
+}
+
+impl<I2C, MODE> TEA5767<I2C, MODE> {
+    /// Get channel plan of current frequency range and channel spacing, both are validated by
+    /// the configuration
+    pub fn channel_plan(&self) -> ChannelPlan {
+        ChannelPlan {
+            numbering: self.channel_numbering,
+            frequency_range: self.frequency_range,
+            channel_spacing: self.channel_spacing,
+        }
+    }
+
+    /// Set channel numbering used by `tune_channel` and `current_channel`
+    pub fn set_channel_numbering(&mut self, numbering: ChannelNumbering) {
+        self.channel_numbering = numbering;
+    }
+
+    /// Get channel of tuned frequency, None when it is not on the channel grid
+    pub fn current_channel(&self) -> Option<u16> {
+        self.channel_plan().channel(self.frequency).ok()
+    }
+}
+
+impl<I2C, E, MODE: Tunable> TEA5767<I2C, MODE>
+where
+    I2C: i2c::Write<Error = E> + i2c::Read<Error = E>
+{
+    /// Tune channel, nothing is written for a channel not in the channel plan
+    pub fn tune_channel(&mut self, channel: u16) -> Result<(), TuneChannelError<E>> {
+        let frequency = self.channel_plan().frequency(channel).map_err(TuneChannelError::Channel)?;
+        self.set_frequency(frequency).map_err(TuneChannelError::Bus)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::channel::*;
+
+    #[test]
+    fn test_fcc_channels() {
+        let plan = ChannelPlan::new(ChannelNumbering::Fcc, BandLimits::EuropeUS, 0.2).unwrap();
+        assert_eq!(plan.frequency(201), Ok(88.1));
+        assert_eq!(plan.frequency(300), Ok(107.9));
+        assert_eq!(plan.frequency(200), Err(ChannelError::OutOfBand));
+        assert_eq!(plan.channel(101.1), Ok(266));
+        assert_eq!(plan.channel(101.0), Err(ChannelError::OffGrid));
+        assert_eq!(plan.channel(87.9), Err(ChannelError::OutOfBand));
+    }
+
+    #[test]
+    fn test_grid_channels() {
+        let plan = ChannelPlan::new(ChannelNumbering::Grid, BandLimits::Japanese, 0.1).unwrap();
+        assert_eq!(plan.channels(), 151);
+        assert_eq!(plan.frequency(0), Ok(76.0));
+        assert_eq!(plan.frequency(150), Ok(91.0));
+        assert_eq!(plan.frequency(151), Err(ChannelError::OutOfBand));
+        assert_eq!(plan.channel(80.5), Ok(45));
+        assert_eq!(plan.channel(80.55), Err(ChannelError::OffGrid));
+        assert_eq!(plan.channel(95.0), Err(ChannelError::OutOfBand));
+    }
+
+    #[test]
+    fn test_invalid_spacing() {
+        assert_eq!(ChannelPlan::new(ChannelNumbering::Grid, BandLimits::EuropeUS, 0.0), None);
+        assert_eq!(ChannelPlan::new(ChannelNumbering::Grid, BandLimits::EuropeUS, f32::NAN), None);
+        assert_eq!(ChannelPlan::new(ChannelNumbering::Grid, BandLimits::Japanese, 16.0), None);
+    }
+}
@@ -0,0 +1,419 @@
+//! Line based command protocol for host control of the tuner
+//!
+//! One command per line, keywords are case insensitive:
+//!
+//! | Command                            | Reply                                 |
+//! |------------------------------------|---------------------------------------|
+//! | `TUNE 101.1`                       | `OK TUNE 101.1`                       |
+//! | `SEEK UP\|DOWN [LOW\|MID\|HIGH]`   | `OK SEEK FOUND 101.3`, `OK SEEK LIMIT 108.0` |
+//! | `SCAN [LOW\|MID\|HIGH]`            | `OK SCAN 3 89.9 95.1 101.3`           |
+//! | `STATUS`                           | `OK STATUS 101.3 STEREO 13`           |
+//! | `MUTE [L\|R]`, `UNMUTE [L\|R]`     | `OK`                                  |
+//! | `MONO`, `STEREO`                   | `OK`                                  |
+//! | `STANDBY ON\|OFF`                  | `OK`                                  |
+//!
+//! Errors are replied as `ERR <reason>`.
+
+use super::defs::*;
+use core::fmt;
+use embedded_hal::blocking::i2c;
+
+/// Maximum number of stations in scan reply
+pub const SCAN_MAX: usize = 32;
+/// Maximum length of command line, bytes
+pub const LINE_MAX: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Command {
+    /// Tune to frequency, MHz
+    Tune(f32),
+    /// Search for next station
+    Seek(SearchModeDirection, Option<SearchAdcLevel>),
+    /// Search whole band for stations
+    Scan(Option<SearchAdcLevel>),
+    /// Read tuner status
+    Status,
+    Mute(MuteChannel),
+    Unmute(MuteChannel),
+    SoundMode(SoundMode),
+    Standby(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum ParseError {
+    /// Empty line
+    Empty,
+    /// Line longer than LINE_MAX or not UTF-8
+    Line,
+    /// Unknown command keyword
+    UnknownCommand,
+    /// Required argument missing
+    MissingArgument,
+    /// Argument value not valid
+    InvalidArgument,
+    /// More arguments than command accepts
+    TooManyArguments,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Reply {
+    /// Command done
+    Ok,
+    /// Tuned frequency, MHz
+    Tuned(f32),
+    /// Search result and frequency the search stopped at, MHz
+    Seek(SearchStatus, f32),
+    /// Stations found by scan, MHz, first `count` are valid
+    Scan { stations: [f32; SCAN_MAX], count: usize },
+    Status(TunerStatus),
+    /// Command not parsed
+    Error(ParseError),
+    /// Device access failed
+    DeviceError,
+}
+
+/// Collects received bytes into command lines
+#[derive(Debug)]
+pub struct LineReader {
+    buffer: [u8; LINE_MAX],
+    length: usize,
+    overflow: bool,
+}
+
+impl Command {
+    /// Parse command from a line without line ending
+    pub fn parse(line: &str) -> Result<Command, ParseError> {
+        let mut words = line.split_whitespace();
+        let keyword = words.next().ok_or(ParseError::Empty)?;
+        let argument = words.next();
+
+        let command = if keyword.eq_ignore_ascii_case("TUNE") {
+            let frequency = argument.ok_or(ParseError::MissingArgument)?
+                .parse::<f32>().map_err(|_| ParseError::InvalidArgument)?;
+            if !frequency.is_finite() {
+                return Err(ParseError::InvalidArgument);
+            }
+            Command::Tune(frequency)
+        } else if keyword.eq_ignore_ascii_case("SEEK") {
+            let direction = match argument.ok_or(ParseError::MissingArgument)? {
+                a if a.eq_ignore_ascii_case("UP") => SearchModeDirection::Up,
+                a if a.eq_ignore_ascii_case("DOWN") => SearchModeDirection::Down,
+                _ => return Err(ParseError::InvalidArgument),
+            };
+            Command::Seek(direction, parse_level(words.next())?)
+        } else if keyword.eq_ignore_ascii_case("SCAN") {
+            Command::Scan(parse_level(argument)?)
+        } else if keyword.eq_ignore_ascii_case("STATUS") {
+            no_argument(argument, Command::Status)?
+        } else if keyword.eq_ignore_ascii_case("MUTE") {
+            Command::Mute(parse_channel(argument)?)
+        } else if keyword.eq_ignore_ascii_case("UNMUTE") {
+            Command::Unmute(parse_channel(argument)?)
+        } else if keyword.eq_ignore_ascii_case("MONO") {
+            no_argument(argument, Command::SoundMode(SoundMode::Mono))?
+        } else if keyword.eq_ignore_ascii_case("STEREO") {
+            no_argument(argument, Command::SoundMode(SoundMode::Stereo))?
+        } else if keyword.eq_ignore_ascii_case("STANDBY") {
+            match argument.ok_or(ParseError::MissingArgument)? {
+                a if a.eq_ignore_ascii_case("ON") => Command::Standby(true),
+                a if a.eq_ignore_ascii_case("OFF") => Command::Standby(false),
+                _ => return Err(ParseError::InvalidArgument),
+            }
+        } else {
+            return Err(ParseError::UnknownCommand);
+        };
+
+        match words.next() {
+            Some(_) => Err(ParseError::TooManyArguments),
+            None => Ok(command),
+        }
+    }
+
+    /// Run command on the tuner
+    pub fn execute<I2C, E>(&self, tuner: &mut TEA5767<I2C>) -> Result<Reply, E>
+    where I2C: i2c::Write<Error = E> + i2c::Read<Error = E>
+    {
+        let reply = match *self {
+            Command::Tune(frequency) => {
+                tuner.set_frequency(frequency)?;
+                Reply::Tuned(tuner.get_frequency()?)
+            }
+            Command::Seek(direction, level) => {
+                let level = level.unwrap_or(tuner.search_adc_level);
+                let from_frequency = tuner.frequency;
+                let status = match direction {
+                    SearchModeDirection::Up => tuner.search_up(level, from_frequency)?,
+                    SearchModeDirection::Down => tuner.search_down(level, from_frequency)?,
+                };
+                Reply::Seek(status, tuner.get_frequency()?)
+            }
+            Command::Scan(level) => {
+                let level = level.unwrap_or(tuner.search_adc_level);
+                let mut stations = [0.0; SCAN_MAX];
+                let mut count = 0;
+                let mut from_frequency = tuner.frequency_range.0;
+                while count < SCAN_MAX
+                    && tuner.search_up(level, from_frequency)? == SearchStatus::Success {
+                    from_frequency = tuner.get_frequency()?;
+                    stations[count] = from_frequency;
+                    count += 1;
+                }
+                Reply::Scan { stations, count }
+            }
+            Command::Status => Reply::Status(tuner.get_status()?),
+            Command::Mute(channel) => {
+                match channel {
+                    MuteChannel::Left => tuner.mute_left()?,
+                    MuteChannel::Right => tuner.mute_right()?,
+                    _ => tuner.mute()?,
+                }
+                Reply::Ok
+            }
+            Command::Unmute(channel) => {
+                match channel {
+                    MuteChannel::Left => tuner.unmute_left()?,
+                    MuteChannel::Right => tuner.unmute_right()?,
+                    _ => tuner.unmute()?,
+                }
+                Reply::Ok
+            }
+            Command::SoundMode(SoundMode::Mono) => {
+                tuner.set_mono()?;
+                Reply::Ok
+            }
+            Command::SoundMode(SoundMode::Stereo) => {
+                tuner.set_stereo()?;
+                Reply::Ok
+            }
+            Command::Standby(standby) => {
+                match standby {
+                    true => tuner.set_standby()?,
+                    false => tuner.reset_standby()?,
+                }
+                Reply::Ok
+            }
+        };
+        Ok(reply)
+    }
+}
+
+/// Parse and run command line, errors are turned into replies
+pub fn process<I2C, E>(line: &str, tuner: &mut TEA5767<I2C>) -> Reply
+where I2C: i2c::Write<Error = E> + i2c::Read<Error = E>
+{
+    match Command::parse(line) {
+        Ok(command) => command.execute(tuner).unwrap_or(Reply::DeviceError),
+        Err(error) => Reply::Error(error),
+    }
+}
+
+impl LineReader {
+    pub fn new() -> Self {
+        LineReader {
+            buffer: [0; LINE_MAX],
+            length: 0,
+            overflow: false,
+        }
+    }
+
+    /// Add received byte, a command is returned at the end of a non-empty line
+    pub fn push(&mut self, byte: u8) -> Option<Result<Command, ParseError>> {
+        if byte != b'\n' && byte != b'\r' {
+            if self.length < LINE_MAX {
+                self.buffer[self.length] = byte;
+                self.length += 1;
+            } else {
+                self.overflow = true;
+            }
+            return None;
+        }
+
+        let result = match (self.overflow, core::str::from_utf8(&self.buffer[..self.length])) {
+            (false, Ok(line)) => Command::parse(line),
+            _ => Err(ParseError::Line),
+        };
+        self.length = 0;
+        self.overflow = false;
+        match result {
+            Err(ParseError::Empty) => None,
+            result => Some(result),
+        }
+    }
+}
+
+impl Default for LineReader {
+    fn default() -> Self {
+        LineReader::new()
+    }
+}
+
+impl fmt::Display for Reply {
+    /// Reply line without line ending
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reply::Ok => write!(f, "OK"),
+            Reply::Tuned(frequency) => write!(f, "OK TUNE {:.1}", frequency),
+            Reply::Seek(status, frequency) => {
+                let status = match status {
+                    SearchStatus::Success => "FOUND",
+                    SearchStatus::Failure => "LIMIT",
+                };
+                write!(f, "OK SEEK {} {:.1}", status, frequency)
+            }
+            Reply::Scan { stations, count } => {
+                write!(f, "OK SCAN {}", count)?;
+                for station in stations.iter().take(*count) {
+                    write!(f, " {:.1}", station)?;
+                }
+                Ok(())
+            }
+            Reply::Status(status) => {
+                let sound_mode = match status.sound_mode {
+                    SoundMode::Stereo => "STEREO",
+                    SoundMode::Mono => "MONO",
+                };
+                write!(f, "OK STATUS {:.1} {} {}", status.frequency, sound_mode,
+                       status.signal_level)
+            }
+            Reply::Error(error) => {
+                let reason = match error {
+                    ParseError::Empty => "EMPTY",
+                    ParseError::Line => "LINE",
+                    ParseError::UnknownCommand => "UNKNOWN",
+                    ParseError::MissingArgument => "MISSING",
+                    ParseError::InvalidArgument => "INVALID",
+                    ParseError::TooManyArguments => "ARGUMENTS",
+                };
+                write!(f, "ERR {}", reason)
+            }
+            Reply::DeviceError => write!(f, "ERR DEVICE"),
+        }
+    }
+}
+
+fn parse_level(argument: Option<&str>) -> Result<Option<SearchAdcLevel>, ParseError> {
+    match argument {
+        None => Ok(None),
+        Some(a) if a.eq_ignore_ascii_case("LOW") => Ok(Some(SearchAdcLevel::Low)),
+        Some(a) if a.eq_ignore_ascii_case("MID") => Ok(Some(SearchAdcLevel::Mid)),
+        Some(a) if a.eq_ignore_ascii_case("HIGH") => Ok(Some(SearchAdcLevel::High)),
+        Some(_) => Err(ParseError::InvalidArgument),
+    }
+}
+
+fn parse_channel(argument: Option<&str>) -> Result<MuteChannel, ParseError> {
+    match argument {
+        None => Ok(MuteChannel::Both),
+        Some(a) if a.eq_ignore_ascii_case("L") => Ok(MuteChannel::Left),
+        Some(a) if a.eq_ignore_ascii_case("R") => Ok(MuteChannel::Right),
+        Some(_) => Err(ParseError::InvalidArgument),
+    }
+}
+
+fn no_argument(argument: Option<&str>, command: Command) -> Result<Command, ParseError> {
+    match argument {
+        Some(_) => Err(ParseError::TooManyArguments),
+        None => Ok(command),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::*;
+    use core::fmt::Write;
+
+    // Fixed size reply buffer
+    struct Line {
+        buffer: [u8; 64],
+        length: usize,
+    }
+
+    impl Write for Line {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let end = self.length + s.len();
+            if end > self.buffer.len() {
+                return Err(fmt::Error);
+            }
+            self.buffer[self.length..end].copy_from_slice(s.as_bytes());
+            self.length = end;
+            Ok(())
+        }
+    }
+
+    fn format(reply: Reply) -> Line {
+        let mut line = Line { buffer: [0; 64], length: 0 };
+        write!(line, "{}", reply).unwrap();
+        line
+    }
+
+    fn assert_reply(reply: Reply, expected: &str) {
+        let line = format(reply);
+        assert_eq!(core::str::from_utf8(&line.buffer[..line.length]).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(Command::parse("TUNE 101.1"), Ok(Command::Tune(101.1)));
+        assert_eq!(Command::parse("  seek  up high "),
+                   Ok(Command::Seek(SearchModeDirection::Up, Some(SearchAdcLevel::High))));
+        assert_eq!(Command::parse("SEEK DOWN"), Ok(Command::Seek(SearchModeDirection::Down, None)));
+        assert_eq!(Command::parse("SCAN"), Ok(Command::Scan(None)));
+        assert_eq!(Command::parse("STATUS"), Ok(Command::Status));
+        assert_eq!(Command::parse("MUTE L"), Ok(Command::Mute(MuteChannel::Left)));
+        assert_eq!(Command::parse("UNMUTE"), Ok(Command::Unmute(MuteChannel::Both)));
+        assert_eq!(Command::parse("Mono"), Ok(Command::SoundMode(SoundMode::Mono)));
+        assert_eq!(Command::parse("STANDBY ON"), Ok(Command::Standby(true)));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Command::parse("   "), Err(ParseError::Empty));
+        assert_eq!(Command::parse("TUNE"), Err(ParseError::MissingArgument));
+        assert_eq!(Command::parse("TUNE abc"), Err(ParseError::InvalidArgument));
+        assert_eq!(Command::parse("TUNE NaN"), Err(ParseError::InvalidArgument));
+        assert_eq!(Command::parse("TUNE inf"), Err(ParseError::InvalidArgument));
+        assert_eq!(Command::parse("SEEK SIDEWAYS"), Err(ParseError::InvalidArgument));
+        assert_eq!(Command::parse("SEEK UP LOW MID"), Err(ParseError::TooManyArguments));
+        assert_eq!(Command::parse("STATUS NOW"), Err(ParseError::TooManyArguments));
+        assert_eq!(Command::parse("MUTE X"), Err(ParseError::InvalidArgument));
+        assert_eq!(Command::parse("RESET"), Err(ParseError::UnknownCommand));
+    }
+
+    #[test]
+    fn test_line_reader() {
+        let mut reader = LineReader::new();
+        let mut commands = b"\r\nSTATUS\r\nTUNE 9".iter().filter_map(|b| reader.push(*b));
+        assert_eq!(commands.next(), Some(Ok(Command::Status)));
+        assert_eq!(commands.next(), None);
+        assert_eq!(reader.push(b'9'), None);
+        assert_eq!(reader.push(b'\n'), Some(Ok(Command::Tune(99.0))));
+
+        for _ in 0..LINE_MAX + 1 {
+            reader.push(b'A');
+        }
+        assert_eq!(reader.push(b'\n'), Some(Err(ParseError::Line)));
+        assert_eq!(reader.push(b'\n'), None);
+    }
+
+    #[test]
+    fn test_replies() {
+        assert_reply(Reply::Ok, "OK");
+        assert_reply(Reply::Tuned(101.1), "OK TUNE 101.1");
+        assert_reply(Reply::Seek(SearchStatus::Failure, 108.0), "OK SEEK LIMIT 108.0");
+        let mut stations = [0.0; SCAN_MAX];
+        stations[..2].copy_from_slice(&[89.9, 95.1]);
+        assert_reply(Reply::Scan { stations, count: 2 }, "OK SCAN 2 89.9 95.1");
+        assert_reply(Reply::Status(TunerStatus {
+            frequency: 101.3,
+            sound_mode: SoundMode::Stereo,
+            signal_level: 13,
+            ready: false,
+            band_limit: false,
+        }), "OK STATUS 101.3 STEREO 13");
+        assert_reply(Reply::Error(ParseError::UnknownCommand), "ERR UNKNOWN");
+    }
+}
@@ -1,6 +1,11 @@
+use core::fmt;
+use core::marker::PhantomData;
+use super::events::EventState;
+use super::channel::ChannelNumbering;
+use super::device::band_range;
+
 /// TEA5767 device driver
-#[derive(Debug)]
-pub struct TEA5767<I2C> {
+pub struct TEA5767<I2C, MODE = Dynamic> {
     pub(crate) i2c: I2C,
     pub(crate) address: u8,
     pub(crate) frequency: f32,
@@ -13,6 +18,7 @@ pub struct TEA5767<I2C> {
     pub(crate) search_adc_level: SearchAdcLevel,
     pub(crate) injection_side: InjectionSide,
     pub(crate) sound_mode: SoundMode,
+    pub(crate) user_sound_mode: Option<SoundMode>,
     pub(crate) high_cut_control: bool,
     pub(crate) stereo_noise_canceling: bool,
     pub(crate) crystal_frequency: CrystalFrequency,
@@ -20,10 +26,110 @@ pub struct TEA5767<I2C> {
     pub(crate) software_programmable_port2: bool,
     pub(crate) search_indicator: bool,
     pub(crate) deemphasis_time: DeemphasisTime,
+    pub(crate) frequency_range: (f32, f32),
+    pub(crate) channel_spacing: f32,
+    pub(crate) seek_step: f32,
+    pub(crate) search_mute_policy: SearchMutePolicy,
+    pub(crate) crystal_ppm: f32,
+    pub(crate) events: EventState,
+    pub(crate) channel_numbering: ChannelNumbering,
+    pub(crate) mode: PhantomData<MODE>,
+}
+
+// Written by hand like the defmt and ufmt output, the I2C bus is left out
+impl<I2C, MODE> fmt::Debug for TEA5767<I2C, MODE> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TEA5767")
+            .field("address", &self.address)
+            .field("frequency", &self.frequency)
+            .field("band_limits", &self.band_limits)
+            .field("standby", &self.standby)
+            .field("mute", &self.mute)
+            .field("soft_mute", &self.soft_mute)
+            .field("sound_mode", &self.sound_mode)
+            .field("high_cut_control", &self.high_cut_control)
+            .field("stereo_noise_canceling", &self.stereo_noise_canceling)
+            .field("crystal_frequency", &self.crystal_frequency)
+            .field("deemphasis_time", &self.deemphasis_time)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Standby mode is switched at runtime, default
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub struct Dynamic;
+
+/// Typestate of active tuner, created with `into_active` or `wake`
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub struct Active;
+
+/// Typestate of tuner in standby, created with `into_standby`. Tuning methods are available
+/// again after `wake`:
+///
+/// ```
+/// # use embedded_hal::blocking::i2c;
+/// # use tea5767::defs::*;
+/// # struct Bus;
+/// # impl i2c::Write for Bus {
+/// #     type Error = ();
+/// #     fn write(&mut self, _: u8, _: &[u8]) -> Result<(), ()> { Ok(()) }
+/// # }
+/// # impl i2c::Read for Bus {
+/// #     type Error = ();
+/// #     fn read(&mut self, _: u8, _: &mut [u8]) -> Result<(), ()> { Ok(()) }
+/// # }
+/// let tuner = TEA5767::new(Bus, 95.1, BandLimits::EuropeUS, SoundMode::Stereo)?;
+/// let mut tuner = tuner.into_standby()?.wake()?;
+/// tuner.set_frequency(101.3)?;
+/// # Ok::<(), ()>(())
+/// ```
+///
+/// A tuner in standby cannot be tuned:
+///
+/// ```compile_fail
+/// # use embedded_hal::blocking::i2c;
+/// # use tea5767::defs::*;
+/// # struct Bus;
+/// # impl i2c::Write for Bus {
+/// #     type Error = ();
+/// #     fn write(&mut self, _: u8, _: &[u8]) -> Result<(), ()> { Ok(()) }
+/// # }
+/// # impl i2c::Read for Bus {
+/// #     type Error = ();
+/// #     fn read(&mut self, _: u8, _: &mut [u8]) -> Result<(), ()> { Ok(()) }
+/// # }
+/// let tuner = TEA5767::new(Bus, 95.1, BandLimits::EuropeUS, SoundMode::Stereo)?;
+/// let mut tuner = tuner.into_standby()?;
+/// tuner.set_frequency(101.3)?;
+/// # Ok::<(), ()>(())
+/// ```
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub struct Standby;
+
+/// Modes in which tuning methods are available
+pub trait Tunable: private::Sealed {}
+
+impl Tunable for Dynamic {}
+impl Tunable for Active {}
+
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for super::Dynamic {}
+    impl Sealed for super::Active {}
 }
 
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SearchModeDirection {
     /// Up, default
     Up,
@@ -32,6 +138,9 @@ pub enum SearchModeDirection {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SearchAdcLevel {
     /// low ADC output = 5
     Low,
@@ -42,18 +151,27 @@ pub enum SearchAdcLevel {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum InjectionSide {
     LowSide,
     HighSide,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SoundMode {
     Stereo,
     Mono,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MuteChannel {
     Right,
     Left,
@@ -62,11 +180,30 @@ pub enum MuteChannel {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SearchMutePolicy {
+    /// Mute both channels during search, default
+    Mute,
+    /// Enable soft mute during search
+    SoftMute,
+    /// Keep audio unchanged during search
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BandLimits {
     Japanese,
     EuropeUS,
 }
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CrystalFrequency {
     /// 13 Mhz
     Clk13Mhz,
@@ -77,6 +214,9 @@ pub enum CrystalFrequency {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DeemphasisTime {
     /// 75 μs
     Dtc75,
@@ -85,6 +225,197 @@ pub enum DeemphasisTime {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Region {
+    /// 87.5 - 108.0 MHz, 50 μs, 100 kHz grid
+    Europe,
+    /// 87.9 - 107.9 MHz, 75 μs, 200 kHz grid
+    USA,
+    /// 76.0 - 90.0 MHz, 50 μs, 100 kHz grid
+    Japan,
+    /// 88.1 - 107.9 MHz, 75 μs, 200 kHz grid
+    Korea,
+    /// 87.5 - 107.9 MHz, 50 μs, 200 kHz grid
+    Australia,
+}
+
+/// Band limits, de-emphasis and channel grid of a region
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RegionProfile {
+    pub band_limits: BandLimits,
+    /// tunable frequency range (min, max), MHz
+    pub frequency_range: (f32, f32),
+    pub deemphasis_time: DeemphasisTime,
+    /// distance between neighbouring channels, MHz
+    pub channel_spacing: f32,
+    /// frequency step of the software search, MHz
+    pub seek_step: f32,
+}
+
+impl Region {
+    /// Get profile applied by `set_region`
+    pub fn profile(self) -> RegionProfile {
+        match self {
+            Region::Europe => RegionProfile {
+                band_limits: BandLimits::EuropeUS,
+                frequency_range: BAND_LIMITS_EUROPE_US,
+                deemphasis_time: DeemphasisTime::Dtc50,
+                channel_spacing: 0.1,
+                seek_step: 0.1,
+            },
+            Region::USA => RegionProfile {
+                band_limits: BandLimits::EuropeUS,
+                frequency_range: (87.9, 107.9),
+                deemphasis_time: DeemphasisTime::Dtc75,
+                channel_spacing: 0.2,
+                seek_step: 0.2,
+            },
+            Region::Japan => RegionProfile {
+                band_limits: BandLimits::Japanese,
+                frequency_range: (76.0, 90.0),
+                deemphasis_time: DeemphasisTime::Dtc50,
+                channel_spacing: 0.1,
+                seek_step: 0.1,
+            },
+            Region::Korea => RegionProfile {
+                band_limits: BandLimits::EuropeUS,
+                frequency_range: (88.1, 107.9),
+                deemphasis_time: DeemphasisTime::Dtc75,
+                channel_spacing: 0.2,
+                seek_step: 0.2,
+            },
+            Region::Australia => RegionProfile {
+                band_limits: BandLimits::EuropeUS,
+                frequency_range: (87.5, 107.9),
+                deemphasis_time: DeemphasisTime::Dtc50,
+                channel_spacing: 0.2,
+                seek_step: 0.2,
+            },
+        }
+    }
+}
+
+/// Software search criteria, all of them must be met to stop at a channel
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SearchCriteria {
+    /// minimum ADC output level, 0 - 15, higher levels are treated as 15
+    pub adc_level: u8,
+    /// accepted IF counter range (min, max), 0x31 - 0x3E for a correctly tuned station
+    pub if_counter: (u8, u8),
+    /// stop only on stations received in stereo
+    pub stereo: bool,
+    /// time spent on each channel before it is measured, ms
+    pub dwell_ms: u16,
+}
+
+impl Default for SearchCriteria {
+    fn default() -> Self {
+        SearchCriteria {
+            adc_level: 7,
+            if_counter: IF_COUNTER_TUNED,
+            stereo: false,
+            dwell_ms: 10,
+        }
+    }
+}
+
+/// Tuner configuration, everything but the tuned frequency
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TunerConfig {
+    pub band_limits: BandLimits,
+    /// tunable frequency range (min, max), MHz
+    pub frequency_range: (f32, f32),
+    /// distance between neighbouring channels, MHz
+    pub channel_spacing: f32,
+    /// frequency step of the software search, MHz
+    pub seek_step: f32,
+    pub deemphasis_time: DeemphasisTime,
+    pub sound_mode: SoundMode,
+    pub mute: MuteChannel,
+    pub soft_mute: bool,
+    pub high_cut_control: bool,
+    pub stereo_noise_canceling: bool,
+    pub crystal_frequency: CrystalFrequency,
+    pub injection_side: InjectionSide,
+    pub search_adc_level: SearchAdcLevel,
+    pub search_mute_policy: SearchMutePolicy,
+}
+
+impl Default for TunerConfig {
+    /// Configuration set by `TEA5767::new` for Europe/US band in stereo
+    fn default() -> Self {
+        TunerConfig {
+            band_limits: BandLimits::EuropeUS,
+            frequency_range: BAND_LIMITS_EUROPE_US,
+            channel_spacing: CHANNEL_SPACING,
+            seek_step: CHANNEL_SPACING,
+            deemphasis_time: DeemphasisTime::Dtc75,
+            sound_mode: SoundMode::Stereo,
+            mute: MuteChannel::None,
+            soft_mute: false,
+            high_cut_control: true,
+            stereo_noise_canceling: true,
+            crystal_frequency: CrystalFrequency::Clk32_768Khz,
+            injection_side: InjectionSide::HighSide,
+            search_adc_level: SearchAdcLevel::Low,
+            search_mute_policy: SearchMutePolicy::Mute,
+        }
+    }
+}
+
+impl TunerConfig {
+    /// Frequency range lies within the band limits, channel spacing and seek step are at least
+    /// `MIN_CHANNEL_SPACING` and at most the width of the range
+    pub fn is_valid(&self) -> bool {
+        let band = band_range(self.band_limits);
+        let (low, high) = self.frequency_range;
+        let step_valid = |step: f32| step >= MIN_CHANNEL_SPACING && step <= high - low;
+        low >= band.0 && low < high && high <= band.1
+            && step_valid(self.channel_spacing) && step_valid(self.seek_step)
+    }
+}
+
+/// Configuration errors
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum ConfigError<E> {
+    /// Device access failed
+    Bus(E),
+    /// Configuration rejected by `TunerConfig::is_valid`, nothing is written
+    Invalid,
+}
+
+/// Tuner status read from the device
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TunerStatus {
+    /// tuned frequency, MHz
+    pub frequency: f32,
+    /// received sound mode, mono or stereo
+    pub sound_mode: SoundMode,
+    /// ADC output level, 0 - 15
+    pub signal_level: u8,
+    /// station has been found or band limit has been reached
+    pub ready: bool,
+    /// band limit has been reached
+    pub band_limit: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SearchStatus {
     /// Station has been found
     Success,
@@ -95,4 +426,12 @@ pub enum SearchStatus {
 /// Europe/US frequency band limit range (87.5, 108.0) MHz
 pub const BAND_LIMITS_EUROPE_US: (f32, f32) = (87.5, 108.0);
 /// Japanese frequency band limit range  (76.0, 91.0) MHz
-pub const BAND_LIMITS_JAPANESE: (f32, f32) = (76.0, 91.0);
\ No newline at end of file
+pub const BAND_LIMITS_JAPANESE: (f32, f32) = (76.0, 91.0);
+/// Default channel spacing and software search step, MHz
+pub const CHANNEL_SPACING: f32 = 0.1;
+/// Smallest channel spacing and software search step, searches round to 10 kHz, MHz
+pub const MIN_CHANNEL_SPACING: f32 = 0.01;
+/// IF counter range (0x31, 0x3E) of a correctly tuned station
+pub const IF_COUNTER_TUNED: (u8, u8) = (0x31, 0x3E);
+/// IF counter resolution, Hz per count
+pub const IF_COUNTER_RESOLUTION: f32 = 4096.0;
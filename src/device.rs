@@ -1,9 +1,13 @@
 use super::defs::*;
 use super::regs::*;
 use super::regs::DEVICE_ADDRESS;
+use super::events::EventState;
+use super::channel::ChannelNumbering;
 use embedded_hal::blocking::i2c;
-use bit_field::BitField;
+use embedded_hal::blocking::delay::DelayMs;
+#[allow(unused_imports)] // float methods are inherent with std
 use micromath::F32Ext;
+use core::marker::PhantomData;
 
 // TEA5767 flags and additional information from read mode
 #[derive(Debug)]
@@ -12,9 +16,20 @@ struct TEA5767Flags {
     band_limit_flag: bool,
     sound_mode_flag: SoundMode,
     adc_level: u8,
+    if_counter: u8,
     output_frequency: f32,
 }
 
+impl TEA5767Flags {
+    // Channel meets software search criteria, ADC level is 4 bits wide
+    fn meets(&self, criteria: &SearchCriteria) -> bool {
+        self.adc_level >= criteria.adc_level.min(15)
+            && self.if_counter >= criteria.if_counter.0
+            && self.if_counter <= criteria.if_counter.1
+            && (!criteria.stereo || self.sound_mode_flag == SoundMode::Stereo)
+    }
+}
+
 impl<I2C, E> TEA5767<I2C>
 where
     I2C: i2c::Write<Error = E> + i2c::Read<Error = E>
@@ -22,32 +37,454 @@ where
     /// Create new TEA5767 instance
     pub fn new(i2c: I2C, frequency: f32, band_limits: BandLimits,
                sound_mode: SoundMode) -> Result<Self, E> {
-        let mut tea5767 = TEA5767 {
+        let config = TunerConfig {
+            band_limits,
+            frequency_range: band_range(band_limits),
+            sound_mode,
+            ..Default::default()
+        };
+        let mut tea5767 = TEA5767::from_config(i2c, frequency, &config);
+
+        tea5767.upload()?;
+        Ok(tea5767)
+    }
+
+    /// Create TEA5767 instance for a device which is already running, e.g. after MCU reset.
+    /// Frequency is read from the device, the rest is taken from config and nothing is written
+    pub fn attach(i2c: I2C, config: TunerConfig) -> Result<Self, ConfigError<E>> {
+        if !config.is_valid() {
+            return Err(ConfigError::Invalid);
+        }
+        let mut tea5767 = TEA5767::from_config(i2c, config.frequency_range.0, &config);
+
+        let flags = tea5767.download().map_err(ConfigError::Bus)?;
+        tea5767.frequency = flags.output_frequency;
+        Ok(tea5767)
+    }
+
+    // Driver state for configuration, nothing is written to the device
+    fn from_config(i2c: I2C, frequency: f32, config: &TunerConfig) -> Self {
+        TEA5767 {
             i2c,
             address: DEVICE_ADDRESS,
             frequency,
-            band_limits,
+            band_limits: config.band_limits,
             standby: false,
-            mute: MuteChannel::None,
-            soft_mute: false,
+            mute: config.mute,
+            soft_mute: config.soft_mute,
             search_mode: false,
             search_mode_dir: SearchModeDirection::Up,
-            search_adc_level: SearchAdcLevel::Low,
-            injection_side: InjectionSide::HighSide,
-            sound_mode,
-            high_cut_control: true,
-            stereo_noise_canceling: true,
-            crystal_frequency: CrystalFrequency::Clk32_768Khz,
+            search_adc_level: config.search_adc_level,
+            injection_side: config.injection_side,
+            sound_mode: config.sound_mode,
+            user_sound_mode: None,
+            high_cut_control: config.high_cut_control,
+            stereo_noise_canceling: config.stereo_noise_canceling,
+            crystal_frequency: config.crystal_frequency,
             software_programmable_port1: false,
             software_programmable_port2: false,
             search_indicator: false,
-            deemphasis_time: DeemphasisTime::Dtc75,
+            deemphasis_time: config.deemphasis_time,
+            frequency_range: config.frequency_range,
+            channel_spacing: config.channel_spacing,
+            seek_step: config.seek_step,
+            search_mute_policy: config.search_mute_policy,
+            crystal_ppm: 0.0,
+            events: EventState::default(),
+            channel_numbering: ChannelNumbering::Grid,
+            mode: PhantomData,
+        }
+    }
+
+    /// Enable standby mode
+    pub fn set_standby(&mut self) -> Result<(), E> {
+        self.standby = true;
+        self.upload()
+    }
+
+    /// Disable standby mode
+    pub fn reset_standby(&mut self) -> Result<(), E> {
+        self.standby = false;
+        self.upload()
+    }
+
+    /// Switch to typestate API with active tuner
+    pub fn into_active(mut self) -> Result<TEA5767<I2C, Active>, E> {
+        self.standby = false;
+        self.upload()?;
+        Ok(self.into_mode())
+    }
+
+    /// Switch to typestate API with tuner in standby
+    pub fn into_standby(mut self) -> Result<TEA5767<I2C, Standby>, E> {
+        self.standby = true;
+        self.upload()?;
+        Ok(self.into_mode())
+    }
+}
+
+impl<I2C, E> TEA5767<I2C, Active>
+where
+    I2C: i2c::Write<Error = E> + i2c::Read<Error = E>
+{
+    /// Enable standby mode
+    pub fn into_standby(mut self) -> Result<TEA5767<I2C, Standby>, E> {
+        self.standby = true;
+        self.upload()?;
+        Ok(self.into_mode())
+    }
+}
+
+impl<I2C, E> TEA5767<I2C, Standby>
+where
+    I2C: i2c::Write<Error = E> + i2c::Read<Error = E>
+{
+    /// Disable standby mode
+    pub fn wake(mut self) -> Result<TEA5767<I2C, Active>, E> {
+        self.standby = false;
+        self.upload()?;
+        Ok(self.into_mode())
+    }
+}
+
+impl<I2C, E, MODE: Tunable> TEA5767<I2C, MODE>
+where
+    I2C: i2c::Write<Error = E> + i2c::Read<Error = E>
+{
+    /// Set specific radio frequency
+    pub fn set_frequency(&mut self, frequency: f32) -> Result<(), E> {
+        self.frequency = frequency;
+        self.upload()
+    }
+
+    /// Start searching for radio station up from frequency
+    pub fn search_up(&mut self, signal_level: SearchAdcLevel, from_frequency: f32)
+        -> Result<SearchStatus, E> {
+        // set starting frequency
+        self.set_frequency(from_frequency)?;
+        // mute according to search mute policy
+        self.with_search_mute(|tuner| tuner.search_up_muted(signal_level))
+    }
+
+    // Hardware search up with audio muted
+    fn search_up_muted(&mut self, signal_level: SearchAdcLevel) -> Result<SearchStatus, E> {
+        let mut  status = SearchStatus::Failure;
+        self.upload()?;
+
+        self.search_adc_level = signal_level;
+        self.search_mode_dir = SearchModeDirection::Up;
+
+        let limit = self.frequency_range.1;
+
+        let mut flags = self.download()?;
+
+        while (self.frequency < limit) && (status == SearchStatus::Failure) {
+            self.frequency = flags.output_frequency + 0.1;
+            self.search_mode = true;
+            self.upload()?;
+
+            loop {
+                flags = self.download()?;
+
+                if flags.ready_flag {
+                    // the chip searches up to the band limit, a station beyond the end of
+                    // the frequency range is a band limit as well
+                    if flags.band_limit_flag || !self.in_range(flags.output_frequency) {
+                        self.events.band_limit_reached();
+                        status = SearchStatus::Failure;
+                        self.search_mode = false;
+                        self.upload()?;
+                        break;
+                    } else {
+                        self.search_mode = false;
+                        self.frequency = flags.output_frequency;
+                        status = SearchStatus::Success;
+                        break;
+                    }
+                }
+            }
+        }
+        self.events.search_complete(status, self.frequency);
+        Ok(status)
+    }
+
+    /// Start searching for radio station down from frequency
+    pub fn search_down(&mut self, signal_level: SearchAdcLevel, from_frequency: f32)
+                     -> Result<SearchStatus, E> {
+        // set starting frequency
+        self.set_frequency(from_frequency)?;
+        // mute according to search mute policy
+        self.with_search_mute(|tuner| tuner.search_down_muted(signal_level))
+    }
+
+    // Hardware search down with audio muted
+    fn search_down_muted(&mut self, signal_level: SearchAdcLevel) -> Result<SearchStatus, E> {
+        let mut  status = SearchStatus::Failure;
+        self.upload()?;
+
+        self.search_adc_level = signal_level;
+        self.search_mode_dir = SearchModeDirection::Down;
+
+        let limit = self.frequency_range.0;
+
+        let mut flags = self.download()?;
+
+        while (self.frequency > limit) && (status == SearchStatus::Failure) {
+            self.frequency = flags.output_frequency - 0.1;
+            self.search_mode = true;
+            self.upload()?;
+
+            loop {
+                flags = self.download()?;
+
+                if flags.ready_flag {
+                    // the chip searches up to the band limit, a station beyond the end of
+                    // the frequency range is a band limit as well
+                    if flags.band_limit_flag || !self.in_range(flags.output_frequency) {
+                        self.events.band_limit_reached();
+                        status = SearchStatus::Failure;
+                        self.search_mode = false;
+                        self.upload()?;
+                        break;
+                    } else {
+                        self.search_mode = false;
+                        self.frequency = flags.output_frequency;
+                        status = SearchStatus::Success;
+                        break;
+                    }
+                }
+            }
+        }
+        self.events.search_complete(status, self.frequency);
+        Ok(status)
+    }
+
+    /// Software search for radio station from frequency, the PLL is stepped channel by channel
+    /// and every channel is measured against search criteria. The start frequency is tuned
+    /// again when no channel up to the end of the range meets them
+    pub fn search_software<D>(&mut self, direction: SearchModeDirection,
+                              criteria: SearchCriteria, from_frequency: f32, delay: &mut D)
+        -> Result<SearchStatus, E>
+    where D: DelayMs<u16>
+    {
+        // set starting frequency
+        self.set_frequency(from_frequency)?;
+        // mute according to search mute policy
+        self.with_search_mute(|tuner| tuner.search_software_muted(direction, criteria, delay))
+    }
+
+    // Software search from tuned frequency with audio muted
+    fn search_software_muted<D>(&mut self, direction: SearchModeDirection,
+                                criteria: SearchCriteria, delay: &mut D)
+        -> Result<SearchStatus, E>
+    where D: DelayMs<u16>
+    {
+        let mut status = SearchStatus::Failure;
+        let from_frequency = self.frequency;
+        self.upload()?;
+
+        let (low, high) = self.frequency_range;
+
+        let step = match direction {
+            SearchModeDirection::Up => self.seek_step,
+            SearchModeDirection::Down => -self.seek_step,
         };
 
-        tea5767.upload()?;
-        Ok(tea5767)
+        let mut frequency = self.frequency;
+
+        loop {
+            frequency = ((frequency + step) * 100.0).round() / 100.0;
+            if frequency < low || frequency > high {
+                self.events.band_limit_reached();
+                break;
+            }
+
+            self.frequency = frequency;
+            self.upload()?;
+            delay.delay_ms(criteria.dwell_ms);
+
+            let flags = self.download()?;
+            if flags.meets(&criteria) {
+                status = SearchStatus::Success;
+                break;
+            }
+        }
+        if status == SearchStatus::Failure {
+            self.frequency = from_frequency;
+        }
+        self.events.search_complete(status, self.frequency);
+        Ok(status)
+    }
+
+    /// Measure every channel of the frequency range and tune to the strongest one meeting
+    /// search criteria, tuned frequency is kept if no channel meets them
+    pub fn search_strongest<D>(&mut self, criteria: SearchCriteria, delay: &mut D)
+        -> Result<SearchStatus, E>
+    where D: DelayMs<u16>
+    {
+        // mute according to search mute policy
+        self.with_search_mute(|tuner| tuner.search_strongest_muted(criteria, delay))
+    }
+
+    // Measure every channel with audio muted
+    fn search_strongest_muted<D>(&mut self, criteria: SearchCriteria, delay: &mut D)
+        -> Result<SearchStatus, E>
+    where D: DelayMs<u16>
+    {
+        let from_frequency = self.frequency;
+        self.upload()?;
+
+        let (low, high) = self.frequency_range;
+        let mut strongest: Option<(f32, u8)> = None;
+        let mut frequency = low;
+
+        while frequency <= high {
+            self.frequency = frequency;
+            self.upload()?;
+            delay.delay_ms(criteria.dwell_ms);
+
+            let flags = self.download()?;
+            if flags.meets(&criteria) && strongest.map_or(true, |s| flags.adc_level > s.1) {
+                strongest = Some((frequency, flags.adc_level));
+            }
+            frequency = ((frequency + self.seek_step) * 100.0).round() / 100.0;
+        }
+
+        // restoring mute state tunes the chosen frequency
+        self.frequency = strongest.map_or(from_frequency, |s| s.0);
+        let status = match strongest {
+            Some(_) => SearchStatus::Success,
+            None => SearchStatus::Failure,
+        };
+        self.events.search_complete(status, self.frequency);
+        Ok(status)
+    }
+
+    /// Measure another channel and return to the tuned frequency, audio is muted according to
+    /// search mute policy and both retunes are single register writes
+    pub fn measure_channel<D>(&mut self, frequency: f32, settle_ms: u16, delay: &mut D)
+        -> Result<ReadRegisters, E>
+    where D: DelayMs<u16>
+    {
+        // restoring mute state tunes back, also when the measurement fails
+        self.with_search_mute(|tuner| {
+            let tuned_frequency = tuner.frequency;
+            tuner.frequency = frequency;
+            tuner.upload()?;
+            delay.delay_ms(settle_ms);
+            let registers = tuner.read_registers();
+            tuner.frequency = tuned_frequency;
+            registers
+        })
+    }
+
+    /// Estimate crystal frequency offset from the IF counter on known strong stations. The
+    /// correction is stored and applied to all later PLL calculations, stations with IF counter
+    /// out of the tuned range are skipped. If all of them are skipped the correction is kept
+    /// and None returned
+    ///
+    /// One IF counter count is 4096 Hz, about 40 ppm at 100 MHz, and limits the resolution of
+    /// the estimate. Averaging several stations does not improve it as all readings are
+    /// quantized alike, so an offset within one count is not measurable and estimated as 0
+    pub fn calibrate_crystal<D>(&mut self, stations: &[f32], settle_ms: u16, delay: &mut D)
+        -> Result<Option<f32>, E>
+    where D: DelayMs<u16>
+    {
+        let f_ref = reference_frequency(self.crystal_frequency);
+        let mut offset_sum = 0.0;
+        let mut resolution_sum = 0.0;
+        let mut count = 0;
+
+        for &frequency in stations {
+            let registers = self.measure_channel(frequency, settle_ms, delay)?;
+            if registers.if_counter < IF_COUNTER_TUNED.0
+                || registers.if_counter > IF_COUNTER_TUNED.1
+            {
+                continue;
+            }
+
+            // oscillator frequency follows from the station and the measured IF
+            let intermediate = registers.if_counter as f32 * IF_COUNTER_RESOLUTION;
+            let oscillator = match self.injection_side {
+                InjectionSide::HighSide => frequency * 1_000_000.0 + intermediate,
+                InjectionSide::LowSide => frequency * 1_000_000.0 - intermediate,
+            };
+            let nominal = registers.pll as f32 * f_ref / 4.0;
+            offset_sum += (oscillator / nominal - 1.0) * 1_000_000.0;
+            resolution_sum += IF_COUNTER_RESOLUTION / nominal * 1_000_000.0;
+            count += 1;
+        }
+
+        if count == 0 {
+            return Ok(None);
+        }
+        let offset = offset_sum / count as f32;
+        self.crystal_ppm = if offset.abs() < resolution_sum / count as f32 { 0.0 } else { offset };
+        self.upload()?;
+        Ok(Some(self.crystal_ppm))
+    }
+
+    /// Find reference clock fitted to the module. Every `CrystalFrequency` setting is tried on
+    /// the reference frequency, which should carry a strong station. Preset tuning does not set the
+    /// ready flag, so a setting locks when the IF counter reports the station within the tuned
+    /// range. The setting with IF counter closest to the nominal IF is kept, if none of them
+    /// locks the setting is kept and None returned
+    pub fn detect_clock<D>(&mut self, reference: f32, settle_ms: u16, delay: &mut D)
+        -> Result<Option<CrystalFrequency>, E>
+    where D: DelayMs<u16>
+    {
+        let clock_frequency = self.crystal_frequency;
+        // restoring mute state tunes back with the detected setting, the setting is kept when
+        // the detection fails on the bus
+        self.with_search_mute(|tuner| {
+            let tuned_frequency = tuner.frequency;
+            let mut detected: Option<(CrystalFrequency, f32)> = None;
+
+            tuner.frequency = reference;
+            for &candidate in [CrystalFrequency::Clk32_768Khz, CrystalFrequency::Clk13Mhz,
+                CrystalFrequency::Clk6_5MHz].iter() {
+                match tuner.clock_distance(candidate, settle_ms, delay) {
+                    Ok(Some(distance)) if detected.map_or(true, |d| distance < d.1) => {
+                        detected = Some((candidate, distance));
+                    }
+                    Ok(_) => (),
+                    Err(error) => {
+                        tuner.crystal_frequency = clock_frequency;
+                        return Err(error);
+                    }
+                }
+            }
+
+            tuner.crystal_frequency = detected.map_or(clock_frequency, |d| d.0);
+            tuner.frequency = tuned_frequency;
+            Ok(detected.map(|d| d.0))
+        })
     }
 
+    // Distance of measured IF from nominal 225 kHz with clock setting, Hz. None when the PLL
+    // does not lock
+    fn clock_distance<D>(&mut self, clock: CrystalFrequency, settle_ms: u16, delay: &mut D)
+        -> Result<Option<f32>, E>
+    where D: DelayMs<u16>
+    {
+        self.crystal_frequency = clock;
+        self.upload()?;
+        delay.delay_ms(settle_ms);
+
+        let flags = self.download()?;
+        let locked = flags.if_counter >= IF_COUNTER_TUNED.0
+            && flags.if_counter <= IF_COUNTER_TUNED.1;
+        if !locked {
+            return Ok(None);
+        }
+        Ok(Some((flags.if_counter as f32 * IF_COUNTER_RESOLUTION - 225_000.0).abs()))
+    }
+}
+
+impl<I2C, E, MODE> TEA5767<I2C, MODE>
+where
+    I2C: i2c::Write<Error = E> + i2c::Read<Error = E>
+{
     /// Remove TEA5767 instance
     pub fn destroy(self) -> Result<I2C, E> {
         Ok(self.i2c)
@@ -97,22 +534,36 @@ where
         self.upload()
     }
 
-    /// Enable standby mode
-    pub fn set_standby(&mut self) -> Result<(), E> {
-        self.standby = true;
+    /// Set band: Europe/US or Japanese
+    pub fn set_band(&mut self, band: BandLimits) -> Result<(), E> {
+        self.band_limits = band;
+        self.frequency_range = band_range(band);
         self.upload()
     }
 
-    /// Disable standby mode
-    pub fn reset_standby(&mut self) -> Result<(), E> {
-        self.standby = false;
-        self.upload()
+    /// Set band limits, frequency range, de-emphasis and channel grid of a region,
+    /// current frequency is moved onto the region channel grid
+    pub fn set_region(&mut self, region: Region) -> Result<(), ConfigError<E>> {
+        let profile = region.profile();
+        let config = TunerConfig {
+            band_limits: profile.band_limits,
+            frequency_range: profile.frequency_range,
+            deemphasis_time: profile.deemphasis_time,
+            channel_spacing: profile.channel_spacing,
+            seek_step: profile.seek_step,
+            ..self.config()
+        };
+        if !config.is_valid() {
+            return Err(ConfigError::Invalid);
+        }
+        self.frequency = snap_to_grid(self.frequency, config.frequency_range,
+                                      config.channel_spacing);
+        self.set_config(&config)
     }
 
-    /// Set band: Europe/US or Japanese
-    pub fn set_band(&mut self, band: BandLimits) -> Result<(), E> {
-        self.band_limits = band;
-        self.upload()
+    /// Set how audio is muted while searching, user mute state is restored after search
+    pub fn set_search_mute_policy(&mut self, policy: SearchMutePolicy) {
+        self.search_mute_policy = policy;
     }
 
     /// Enable soft mute mode
@@ -134,6 +585,17 @@ where
         self.upload()
     }
 
+    /// Get crystal frequency offset applied to PLL calculations, ppm
+    pub fn crystal_ppm(&self) -> f32 {
+        self.crystal_ppm
+    }
+
+    /// Set crystal frequency offset, e.g. restored from storage after `calibrate_crystal`, ppm
+    pub fn set_crystal_ppm(&mut self, ppm: f32) -> Result<(), E> {
+        self.crystal_ppm = ppm;
+        self.upload()
+    }
+
     /// Set high cut mode
     pub fn set_high_cut_control(&mut self) -> Result<(), E> {
         self.high_cut_control = true;
@@ -165,119 +627,20 @@ where
         self.upload()
     }
 
-    /// Set specific radio frequency
-    pub fn set_frequency(&mut self, frequency: f32) -> Result<(), E> {
-        self.frequency = frequency;
-        self.upload()
-    }
-
     /// Set stereo sound mode
     pub fn set_stereo(&mut self) -> Result<(), E> {
         self.sound_mode = SoundMode::Stereo;
+        self.user_sound_mode = None;
         self.upload()
     }
+
     /// Set mono sound mode
     pub fn set_mono(&mut self) -> Result<(), E> {
         self.sound_mode = SoundMode::Mono;
+        self.user_sound_mode = None;
         self.upload()
     }
 
-    /// Start searching for radio station up from frequency
-    pub fn search_up(&mut self, signal_level: SearchAdcLevel, from_frequency: f32)
-        -> Result<SearchStatus, E> {
-        let mut  status = SearchStatus::Failure;
-        let _freq: f32 = 0.0;
-
-        // set starting frequency
-        self.set_frequency(from_frequency)?;
-        // mute
-        self.mute()?;
-
-        self.search_adc_level = signal_level;
-        self.search_mode_dir = SearchModeDirection::Up;
-
-        let limit = match self.band_limits {
-            BandLimits::EuropeUS => BAND_LIMITS_EUROPE_US.1,
-            BandLimits::Japanese => BAND_LIMITS_JAPANESE.1,
-        };
-
-        let mut flags = self.download()?;
-
-        while (self.frequency < limit) && (status == SearchStatus::Failure) {
-            self.frequency = flags.output_frequency + 0.1;
-            self.search_mode = true;
-            self.upload()?;
-
-            loop {
-                flags = self.download()?;
-
-                if flags.ready_flag {
-                    if flags.band_limit_flag {
-                        status = SearchStatus::Failure;
-                        self.search_mode = false;
-                        self.upload()?;
-                        break;
-                    } else {
-                        self.search_mode = false;
-                        self.frequency = flags.output_frequency;
-                        status = SearchStatus::Success;
-                        break;
-                    }
-                }
-            }
-        }
-        self.unmute()?;
-        Ok(status)
-    }
-
-    /// Start searching for radio station down from frequency
-    pub fn search_down(&mut self, signal_level: SearchAdcLevel, from_frequency: f32)
-                     -> Result<SearchStatus, E> {
-        let mut  status = SearchStatus::Failure;
-        let _freq: f32 = 0.0;
-
-        // set starting frequency
-        self.set_frequency(from_frequency)?;
-        // mute
-        self.mute()?;
-
-        self.search_adc_level = signal_level;
-        self.search_mode_dir = SearchModeDirection::Down;
-
-        let limit = match self.band_limits {
-            BandLimits::EuropeUS => BAND_LIMITS_EUROPE_US.0,
-            BandLimits::Japanese => BAND_LIMITS_JAPANESE.0,
-        };
-
-        let mut flags = self.download()?;
-
-        while (self.frequency > limit) && (status == SearchStatus::Failure) {
-            self.frequency = flags.output_frequency - 0.1;
-            self.search_mode = true;
-            self.upload()?;
-
-            loop {
-                flags = self.download()?;
-
-                if flags.ready_flag {
-                    if flags.band_limit_flag {
-                        status = SearchStatus::Failure;
-                        self.search_mode = false;
-                        self.upload()?;
-                        break;
-                    } else {
-                        self.search_mode = false;
-                        self.frequency = flags.output_frequency;
-                        status = SearchStatus::Success;
-                        break;
-                    }
-                }
-            }
-        }
-        self.unmute()?;
-        Ok(status)
-    }
-
     /// Get current radio frequency
     pub fn get_frequency(&mut self) -> Result<f32, E> {
         self.download()?;
@@ -297,187 +660,258 @@ where
         Ok(flags.sound_mode_flag)
     }
 
-    // Write preconfigured values to the device registers
-    fn upload(&mut self) -> Result<(), E> {
-        let mut write_bytes: [u8; 5] =  [0; 5];
-        match self.band_limits {
-            BandLimits::EuropeUS => {
-                if self.frequency < BAND_LIMITS_EUROPE_US.0 {
-                    self.frequency = BAND_LIMITS_EUROPE_US.0;
-                }
-                else if self.frequency > BAND_LIMITS_EUROPE_US.1 {
-                    self.frequency = BAND_LIMITS_EUROPE_US.1;
-                }
-            }
-            BandLimits::Japanese => {
-                if self.frequency < BAND_LIMITS_JAPANESE.0 {
-                    self.frequency = BAND_LIMITS_JAPANESE.0;
-                }
-                else if self.frequency > BAND_LIMITS_JAPANESE.1 {
-                    self.frequency = BAND_LIMITS_JAPANESE.1;
-                }
-            }
+    /// Get register image written to the device by the next update
+    pub fn write_registers(&self) -> WriteRegisters {
+        WriteRegisters {
+            mute: self.mute == MuteChannel::Both,
+            search_mode: self.search_mode,
+            pll: to_decimal_pll(self.injection_side, self.crystal_frequency, self.crystal_ppm,
+                                self.frequency).unwrap() as u16,
+            search_up: self.search_mode_dir == SearchModeDirection::Up,
+            search_stop_level: match self.search_adc_level {
+                SearchAdcLevel::Low => 0b01,
+                SearchAdcLevel::Mid => 0b10,
+                SearchAdcLevel::High => 0b11,
+            },
+            high_side_injection: self.injection_side == InjectionSide::HighSide,
+            mono: self.sound_mode == SoundMode::Mono,
+            mute_right: self.mute == MuteChannel::Right,
+            mute_left: self.mute == MuteChannel::Left,
+            port1: self.software_programmable_port1,
+            port2: self.software_programmable_port2,
+            standby: self.standby,
+            japanese_band: self.band_limits == BandLimits::Japanese,
+            xtal: self.crystal_frequency == CrystalFrequency::Clk32_768Khz,
+            soft_mute: self.soft_mute,
+            high_cut_control: self.high_cut_control,
+            stereo_noise_canceling: self.stereo_noise_canceling,
+            search_indicator: self.search_indicator,
+            pllref: self.crystal_frequency == CrystalFrequency::Clk6_5MHz,
+            deemphasis_75us: self.deemphasis_time == DeemphasisTime::Dtc75,
         }
+    }
 
-        let pll = to_register_format_pll(
-            to_decimal_pll(self.injection_side, self.crystal_frequency, self.frequency)
-                .unwrap()
-        ).unwrap();
-
-        write_bytes[0] = pll[0];
-        write_bytes[1] = pll[1];
-
-        match self.mute {
-            MuteChannel::Both => write_bytes.get_mut(0).unwrap()
-                .set_bit(WM_DB1_MUTE, true),
-            _ => write_bytes.get(0).unwrap()
-        };
-
-        match self.search_mode {
-            true => write_bytes.get_mut(0).unwrap()
-                .set_bit(WM_DB1_SM, true),
-            _ => write_bytes.get(0).unwrap()
-        };
-
-        if self.search_mode {
-            self.search_mode = false;
+    /// Read register image from the device
+    pub fn read_registers(&mut self) -> Result<ReadRegisters, E> {
+        Ok(ReadRegisters::from_bytes(read_data(&mut self.i2c, self.address)?))
+    }
+
+    /// Get current configuration
+    pub fn config(&self) -> TunerConfig {
+        TunerConfig {
+            band_limits: self.band_limits,
+            frequency_range: self.frequency_range,
+            channel_spacing: self.channel_spacing,
+            seek_step: self.seek_step,
+            deemphasis_time: self.deemphasis_time,
+            sound_mode: self.sound_mode,
+            mute: self.mute,
+            soft_mute: self.soft_mute,
+            high_cut_control: self.high_cut_control,
+            stereo_noise_canceling: self.stereo_noise_canceling,
+            crystal_frequency: self.crystal_frequency,
+            injection_side: self.injection_side,
+            search_adc_level: self.search_adc_level,
+            search_mute_policy: self.search_mute_policy,
         }
+    }
 
-        match self.search_mode_dir {
-            SearchModeDirection::Up => write_bytes.get_mut(2).unwrap()
-                .set_bit(WM_DB3_SUD, true),
-            _ => write_bytes.get(2).unwrap()
-        };
-
-        match self.search_adc_level {
-            SearchAdcLevel::Low => write_bytes.get_mut(2).unwrap()
-                .set_bits(WM_DB3_SSL, 0b01),
-            SearchAdcLevel::Mid => write_bytes.get_mut(2).unwrap()
-                .set_bits(WM_DB3_SSL, 0b10),
-            SearchAdcLevel::High => write_bytes.get_mut(2).unwrap()
-                .set_bits(WM_DB3_SSL, 0b11),
-        };
-
-        match self.injection_side {
-            InjectionSide::HighSide => write_bytes.get_mut(2).unwrap()
-                .set_bit(WM_DB3_HLSI, true),
-            _ => write_bytes.get(2).unwrap()
-        };
-
-        match self.sound_mode {
-            SoundMode::Mono => write_bytes.get_mut(2).unwrap()
-                .set_bit(WM_DB3_MS, true),
-            _ => write_bytes.get(2).unwrap()
-        };
-
-        match self.mute {
-            MuteChannel::Left => write_bytes.get_mut(2).unwrap()
-                .set_bit(WM_DB3_MR, true),
-            MuteChannel::Right => write_bytes.get_mut(2).unwrap()
-                .set_bit(WM_DB3_ML, true),
-            _ => write_bytes.get(2).unwrap()
-        };
-
-        match self.software_programmable_port1 {
-            true => write_bytes.get_mut(2).unwrap()
-                .set_bit(WM_DB3_SWP1, true),
-            _ => write_bytes.get(2).unwrap()
-        };
-
-        match self.software_programmable_port2 {
-            true => write_bytes.get_mut(3).unwrap()
-                .set_bit(WM_DB4_SWP2, true),
-            _ => write_bytes.get(3).unwrap()
-        };
+    /// Apply whole configuration with a single register write, an invalid configuration is
+    /// rejected before anything is changed
+    pub fn set_config(&mut self, config: &TunerConfig) -> Result<(), ConfigError<E>> {
+        if !config.is_valid() {
+            return Err(ConfigError::Invalid);
+        }
+        self.band_limits = config.band_limits;
+        self.frequency_range = config.frequency_range;
+        self.channel_spacing = config.channel_spacing;
+        self.seek_step = config.seek_step;
+        self.deemphasis_time = config.deemphasis_time;
+        self.sound_mode = config.sound_mode;
+        self.user_sound_mode = None;
+        self.mute = config.mute;
+        self.soft_mute = config.soft_mute;
+        self.high_cut_control = config.high_cut_control;
+        self.stereo_noise_canceling = config.stereo_noise_canceling;
+        self.crystal_frequency = config.crystal_frequency;
+        self.injection_side = config.injection_side;
+        self.search_adc_level = config.search_adc_level;
+        self.search_mute_policy = config.search_mute_policy;
+        self.upload().map_err(ConfigError::Bus)
+    }
+
+    /// Read frequency, sound mode, signal level and search flags at once
+    pub fn get_status(&mut self) -> Result<TunerStatus, E> {
+        let flags = self.download()?;
+        Ok(TunerStatus {
+            frequency: flags.output_frequency,
+            sound_mode: flags.sound_mode_flag,
+            signal_level: flags.adc_level,
+            ready: flags.ready_flag,
+            band_limit: flags.band_limit_flag,
+        })
+    }
+
+    // Run search or measurement with audio muted according to search mute policy, the search
+    // writes the mute itself. The mute state is restored afterwards, also when the search fails
+    // on the bus, which tunes the frequency the search has been started from again.
+    pub(crate) fn with_search_mute<T, F>(&mut self, search: F) -> Result<T, E>
+    where F: FnOnce(&mut Self) -> Result<T, E>
+    {
+        let from_frequency = self.frequency;
+        let mute_state = self.search_mute_state();
+        let result = search(self);
+        if result.is_err() {
+            self.frequency = from_frequency;
+        }
+        let restored = self.restore_mute(mute_state);
+        let value = result?;
+        restored?;
+        Ok(value)
+    }
 
-        match self.standby {
-            true => write_bytes.get_mut(3).unwrap()
-                .set_bit(WM_DB4_STBY, true),
-            _ => write_bytes.get(3).unwrap()
-        };
+    // Frequency within the frequency range
+    fn in_range(&self, frequency: f32) -> bool {
+        frequency >= self.frequency_range.0 && frequency <= self.frequency_range.1
+    }
 
-        match self.high_cut_control {
-            true => write_bytes.get_mut(3).unwrap()
-                .set_bit(WM_DB4_HCC, true),
-            _ => write_bytes.get(3).unwrap()
-        };
+    // Apply search mute policy without writing it to the device
+    pub(crate) fn search_mute_state(&mut self) -> (MuteChannel, bool) {
+        let mute_state = (self.mute, self.soft_mute);
+        match self.search_mute_policy {
+            SearchMutePolicy::Mute => self.mute = MuteChannel::Both,
+            SearchMutePolicy::SoftMute => self.soft_mute = true,
+            SearchMutePolicy::None => (),
+        }
+        mute_state
+    }
 
-        match self.stereo_noise_canceling {
-            true => write_bytes.get_mut(3).unwrap()
-                .set_bit(WM_DB4_SNC, true),
-            _ => write_bytes.get(3).unwrap()
-        };
+    // Restore mute state saved by search_mute
+    pub(crate) fn restore_mute(&mut self, mute_state: (MuteChannel, bool)) -> Result<(), E> {
+        self.mute = mute_state.0;
+        self.soft_mute = mute_state.1;
+        self.upload()
+    }
 
-        match self.search_indicator {
-            true => write_bytes.get_mut(3).unwrap()
-                .set_bit(WM_DB4_SI, true),
-            _ => write_bytes.get(3).unwrap()
-        };
+    // Write preconfigured values to the device registers
+    pub(crate) fn upload(&mut self) -> Result<(), E> {
+        if self.frequency < self.frequency_range.0 {
+            self.frequency = self.frequency_range.0;
+        }
+        else if self.frequency > self.frequency_range.1 {
+            self.frequency = self.frequency_range.1;
+        }
 
-        match self.crystal_frequency {
-            CrystalFrequency::Clk32_768Khz => write_bytes.get_mut(3).unwrap()
-                    .set_bit(WM_DB4_XTAL, true),
-            CrystalFrequency::Clk6_5MHz => write_bytes.get_mut(4).unwrap()
-                .set_bit(WM_DB5_PLLREF, true),
-            _ => write_bytes.get(3).unwrap()
-        };
+        let write_bytes = self.write_registers().to_bytes();
 
-        match self.deemphasis_time {
-            DeemphasisTime::Dtc75 => write_bytes.get_mut(4).unwrap()
-                .set_bit(WM_DB5_DTC, true),
-            _ => write_bytes.get(4).unwrap()
-        };
+        if self.search_mode {
+            self.search_mode = false;
+        }
 
-        write_data(&mut self.i2c, write_bytes)?;
+        write_data(&mut self.i2c, self.address, write_bytes)?;
         Ok(())
     }
 
     // Read actual values from the device registers
     fn download(&mut self) -> Result<TEA5767Flags, E> {
-        let read_bytes = read_data(&mut self.i2c)?;
-
-        let mut flags = TEA5767Flags {
-            ready_flag: false,
-            band_limit_flag: false,
-            sound_mode_flag: SoundMode::Mono,
-            adc_level: 0,
-            output_frequency: 0.0
-        };
-
-        if read_bytes.get(0).unwrap().get_bit(RM_DB1_RF) {
-            flags.ready_flag = true;
-        }
+        let registers = self.read_registers()?;
 
-        if read_bytes.get(0).unwrap().get_bit(RM_DB1_BLF) {
-            flags.band_limit_flag = true;
-        }
-
-        let mut pll = [0_u8; 2];
-        pll[0] = read_bytes[0];
-        pll[1] = read_bytes[1];
-
-        flags.output_frequency = from_decimal_pll(
+        let output_frequency = from_decimal_pll(
             self.injection_side,
             self.crystal_frequency,
-            from_register_format_pll(pll)
-                .unwrap())
+            self.crystal_ppm,
+            registers.pll as u32)
             .unwrap();
 
-        flags.output_frequency = (flags.output_frequency * 10.0).round() / 10.0;
+        Ok(TEA5767Flags {
+            ready_flag: registers.ready,
+            band_limit_flag: registers.band_limit,
+            sound_mode_flag: match registers.stereo {
+                true => SoundMode::Stereo,
+                false => SoundMode::Mono,
+            },
+            adc_level: registers.level,
+            if_counter: registers.if_counter,
+            output_frequency: (output_frequency * 10.0).round() / 10.0,
+        })
+    }
+}
 
-        if read_bytes.get(2).unwrap().get_bit(RM_DB3_STEREO) {
-            flags.sound_mode_flag = SoundMode::Stereo;
+impl<I2C, MODE> TEA5767<I2C, MODE> {
+    // Change typestate, configuration is carried over unchanged
+    pub(crate) fn into_mode<M>(self) -> TEA5767<I2C, M> {
+        let (i2c, tuner) = self.replace_bus::<(), M>(());
+        tuner.rebind(i2c)
+    }
+
+    // Move configuration to driver with another bus, previous bus is returned
+    pub(crate) fn replace_bus<J, M>(self, i2c: J) -> (I2C, TEA5767<J, M>) {
+        let tuner = self.rebind(i2c);
+        (self.i2c, tuner)
+    }
+
+    // Copy configuration to driver with another bus
+    pub(crate) fn rebind<J, M>(&self, i2c: J) -> TEA5767<J, M> {
+        TEA5767 {
+            i2c,
+            address: self.address,
+            frequency: self.frequency,
+            band_limits: self.band_limits,
+            standby: self.standby,
+            mute: self.mute,
+            soft_mute: self.soft_mute,
+            search_mode: self.search_mode,
+            search_mode_dir: self.search_mode_dir,
+            search_adc_level: self.search_adc_level,
+            injection_side: self.injection_side,
+            sound_mode: self.sound_mode,
+            user_sound_mode: self.user_sound_mode,
+            high_cut_control: self.high_cut_control,
+            stereo_noise_canceling: self.stereo_noise_canceling,
+            crystal_frequency: self.crystal_frequency,
+            software_programmable_port1: self.software_programmable_port1,
+            software_programmable_port2: self.software_programmable_port2,
+            search_indicator: self.search_indicator,
+            deemphasis_time: self.deemphasis_time,
+            frequency_range: self.frequency_range,
+            channel_spacing: self.channel_spacing,
+            seek_step: self.seek_step,
+            search_mute_policy: self.search_mute_policy,
+            crystal_ppm: self.crystal_ppm,
+            events: self.events,
+            channel_numbering: self.channel_numbering,
+            mode: PhantomData,
         }
+    }
+}
 
-        flags.adc_level = read_bytes.get(3).unwrap().get_bits(4..8);
+// frequency range of the band limits
+pub(crate) fn band_range(band_limits: BandLimits) -> (f32, f32) {
+    match band_limits {
+        BandLimits::EuropeUS => BAND_LIMITS_EUROPE_US,
+        BandLimits::Japanese => BAND_LIMITS_JAPANESE,
+    }
+}
+
+// move frequency to the nearest channel of the grid starting at the range low end
+pub(crate) fn snap_to_grid(frequency: f32, range: (f32, f32), spacing: f32) -> f32 {
+    let channels = ((range.1 - range.0) / spacing + 0.01).floor();
+    let channel = ((frequency - range.0) / spacing).round().max(0.0).min(channels);
+    ((range.0 + channel * spacing) * 100.0).round() / 100.0
+}
 
-        Ok(flags)
+// nominal reference frequency of the PLL, Hz
+fn reference_frequency(crystal_frequency: CrystalFrequency) -> f32 {
+    match crystal_frequency {
+        CrystalFrequency::Clk32_768Khz => 32_768_f32,
+        CrystalFrequency::Clk6_5MHz => 50_000_f32,
+        CrystalFrequency::Clk13Mhz => 50_000_f32,
     }
 }
 
 // change register binary format to decimal format
 fn to_decimal_pll(injection_side: InjectionSide, crystal_frequency: CrystalFrequency,
-                  frequency: f32) -> Option<u32> {
+                  crystal_ppm: f32, frequency: f32) -> Option<u32> {
     let numerator= match injection_side {
         InjectionSide::HighSide => {
             (4.0 * (frequency * 1_000_000.0 + 225_000.0)) as u32
@@ -487,76 +921,50 @@ fn to_decimal_pll(injection_side: InjectionSide, crystal_frequency: CrystalFrequ
         }
     };
 
-    let f_ref = match crystal_frequency {
-        CrystalFrequency::Clk32_768Khz => 32_768_u32,
-        CrystalFrequency::Clk6_5MHz => 50_000_u32,
-        CrystalFrequency::Clk13Mhz => 50_000_u32,
-    };
+    // reference of a crystal off by ppm, integer for a perfect crystal
+    let f_ref = reference_frequency(crystal_frequency) as f64
+        * (1.0 + crystal_ppm as f64 / 1_000_000.0);
 
-    Some(numerator / f_ref)
+    Some((numerator as f64 / f_ref) as u32)
 }
 
 // get output frequency from device
 fn from_decimal_pll(injection_side: InjectionSide, crystal_frequency: CrystalFrequency,
-                    decimal: u32) -> Option<f32> {
-    let frequency: f32;
-    let f_ref = match crystal_frequency {
-        CrystalFrequency::Clk32_768Khz => 32_768_f32,
-        CrystalFrequency::Clk6_5MHz => 50_000_f32,
-        CrystalFrequency::Clk13Mhz => 50_000_f32,
-    };
-    match injection_side {
+                    crystal_ppm: f32, decimal: u32) -> Option<f32> {
+    let f_ref = reference_frequency(crystal_frequency) * (1.0 + crystal_ppm / 1_000_000.0);
+    let frequency = match injection_side {
         InjectionSide::HighSide => {
-            frequency = ((decimal as f32 * f_ref / 4.0) - 225_000.0) / 1_000_000.0;
+            ((decimal as f32 * f_ref / 4.0) - 225_000.0) / 1_000_000.0
         }
         InjectionSide::LowSide => {
-            frequency = (decimal as f32 * f_ref / 4.0) + 225_000.0 / 1_000_000.0;
+            (decimal as f32 * f_ref / 4.0) + 225_000.0 / 1_000_000.0
         }
-    }
+    };
     Some(frequency)
 }
 
-// change pll decimal format to register binary format
-fn to_register_format_pll(decimal: u32) -> Option<[u8; 2]> {
-    let pll_binary = [decimal.get_bits(8..14) as u8,
-        decimal.get_bits(0..8) as u8];
-    Some(pll_binary)
-}
-
-// change register binary format to decimal format
-fn from_register_format_pll(mut pll: [u8; 2]) -> Option<u32> {
-    pll[0].set_bits(6..8, 0b00);
-    let mut pll_decimal: u32 = 0;
-    // MSB
-    pll_decimal.set_bits(8..14, pll[0] as u32);
-    // LSB
-    pll_decimal.set_bits(0..8, pll[1] as u32);
-
-    Some(pll_decimal)
-}
-
 #[cfg(test)]
 mod tests {
     use crate::device::*;
-    use crate::defs::{InjectionSide, CrystalFrequency};
+    use crate::defs::{InjectionSide, CrystalFrequency, Region};
 
     #[test]
     fn test_to_decimal_pll1() {
         assert_eq!(to_decimal_pll(InjectionSide::HighSide,
                                   CrystalFrequency::Clk32_768Khz,
-                                  89.9).unwrap(), 11001);
+                                  0.0, 89.9).unwrap(), 11001);
     }
     #[test]
     fn test_to_decimal_pll2() {
         assert_eq!(to_decimal_pll(InjectionSide::LowSide,
                                   CrystalFrequency::Clk6_5MHz,
-                                  89.9).unwrap(), 55);
+                                  0.0, 89.9).unwrap(), 55);
     }
     #[test]
     fn test_to_decimal_pll3() {
         assert_eq!(to_decimal_pll(InjectionSide::LowSide,
                                   CrystalFrequency::Clk13Mhz,
-                                  89.9).unwrap(), 27);
+                                  0.0, 89.9).unwrap(), 27);
     }
     #[test]
     fn test_to_register_format_pll() {
@@ -571,7 +979,39 @@ mod tests {
     fn test_from_decimal_format_pll() {
         assert_eq!(from_decimal_pll(InjectionSide::HighSide,
                                     CrystalFrequency::Clk32_768Khz,
-                                    11001).unwrap(),89.895195);
+                                    0.0, 11001).unwrap(),89.895195);
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_decimal_pll_crystal_ppm() {
+        // 100 ppm fast crystal needs one PLL step less
+        assert_eq!(to_decimal_pll(InjectionSide::HighSide,
+                                  CrystalFrequency::Clk32_768Khz,
+                                  100.0, 89.9).unwrap(), 11000);
+        let frequency = from_decimal_pll(InjectionSide::HighSide,
+                                         CrystalFrequency::Clk32_768Khz,
+                                         100.0, 11000).unwrap();
+        assert!((frequency - 89.896).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_snap_to_grid() {
+        assert_eq!(snap_to_grid(100.0, (87.9, 107.9), 0.2), 99.9);
+        assert_eq!(snap_to_grid(101.26, (87.5, 108.0), 0.1), 101.3);
+        assert_eq!(snap_to_grid(70.0, (76.0, 90.0), 0.1), 76.0);
+        assert_eq!(snap_to_grid(110.0, (88.1, 107.9), 0.2), 107.9);
+    }
+
+    #[test]
+    fn test_region_profiles() {
+        for region in [Region::Europe, Region::USA, Region::Japan, Region::Korea,
+            Region::Australia].iter() {
+            let profile = region.profile();
+            let band = band_range(profile.band_limits);
+            let (low, high) = profile.frequency_range;
+            assert!(low >= band.0 && low < high && high <= band.1);
+            assert_eq!(snap_to_grid(low, profile.frequency_range, profile.channel_spacing), low);
+            assert_eq!(snap_to_grid(high, profile.frequency_range, profile.channel_spacing), high);
+        }
+    }
+}
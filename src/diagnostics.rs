@@ -0,0 +1,181 @@
+//! Self-test for production test and field returns
+//!
+//! The test runs on the regular register paths of the driver. PLL readback and band limit
+//! searches are muted following the search mute policy. Tuned frequency, mute and standby
+//! state are restored when it is done, also when it fails on the bus.
+
+use super::defs::*;
+use super::device::band_range;
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::blocking::i2c;
+#[allow(unused_imports)] // float methods are inherent with std
+use micromath::F32Ext;
+
+// Reads polling for the end of a hardware search, 1 ms apart
+const SEARCH_POLLS: u16 = 200;
+
+/// Self-test result, every check passed when true
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SelfTestReport {
+    /// device answered with chip identification of TEA5767
+    pub chip_id: bool,
+    /// PLL word read back after tuning low end, middle and high end of the frequency range
+    pub pll_readback: [bool; 3],
+    /// band limit flag set by search past the high end of the band
+    pub band_limit_high: bool,
+    /// band limit flag set by search past the low end of the band
+    pub band_limit_low: bool,
+    /// tuning kept and signal level unchanged after mute and unmute
+    pub mute: bool,
+    /// tuning kept after standby and wake up
+    pub standby: bool,
+    /// IF counter stuck neither at 0 nor at full scale
+    pub if_counter: bool,
+}
+
+impl SelfTestReport {
+    /// All checks passed
+    pub fn passed(&self) -> bool {
+        self.chip_id
+            && self.pll_readback.iter().all(|&readback| readback)
+            && self.band_limit_high
+            && self.band_limit_low
+            && self.mute
+            && self.standby
+            && self.if_counter
+    }
+}
+
+impl<I2C, E, MODE: Tunable> TEA5767<I2C, MODE>
+where
+    I2C: i2c::Write<Error = E> + i2c::Read<Error = E>
+{
+    /// Exercise the device and report which checks passed
+    pub fn self_test<D>(&mut self, delay: &mut D) -> Result<SelfTestReport, E>
+    where D: DelayMs<u16>
+    {
+        let chip_id = self.read_registers()?.chip_id == 0;
+
+        // restoring mute state tunes back
+        let (pll_readback, if_counter, band_limit_high, band_limit_low) =
+            self.with_search_mute(|tuner| tuner.test_tuning(delay))?;
+
+        delay.delay_ms(10);
+        let mute = self.test_mute(delay)?;
+        let standby = self.test_standby(delay)?;
+
+        Ok(SelfTestReport {
+            chip_id,
+            pll_readback,
+            band_limit_high,
+            band_limit_low,
+            mute,
+            standby,
+            if_counter,
+        })
+    }
+
+    // PLL readback at low end, middle and high end of the frequency range, IF counter and
+    // band limit searches from both band edges
+    fn test_tuning<D>(&mut self, delay: &mut D) -> Result<([bool; 3], bool, bool, bool), E>
+    where D: DelayMs<u16>
+    {
+        let frequency = self.frequency;
+        let (low, high) = self.frequency_range;
+
+        let mut pll_readback = [false; 3];
+        let mut if_counter = true;
+        let middle = ((low + high) / 2.0 * 10.0).round() / 10.0;
+        for (readback, &test_frequency) in pll_readback.iter_mut().zip([low, middle, high].iter()) {
+            self.set_frequency(test_frequency)?;
+            delay.delay_ms(10);
+            let registers = self.read_registers()?;
+            *readback = registers.pll == self.write_registers().pll;
+            if_counter &= registers.if_counter != 0 && registers.if_counter != 0x7F;
+        }
+
+        let band = band_range(self.band_limits);
+        let band_limit_high = self.search_band_limit(band.1, SearchModeDirection::Up, delay)?;
+        let band_limit_low = self.search_band_limit(band.0, SearchModeDirection::Down, delay)?;
+
+        self.frequency = frequency;
+        Ok((pll_readback, if_counter, band_limit_high, band_limit_low))
+    }
+
+    // Tuning kept and signal level unchanged after mute and unmute, audio is unmuted again
+    // when the check fails on the bus
+    fn test_mute<D>(&mut self, delay: &mut D) -> Result<bool, E>
+    where D: DelayMs<u16>
+    {
+        let before = self.read_registers()?;
+
+        let mute_state = self.mute;
+        self.mute = MuteChannel::Both;
+        let muted = self.upload().and_then(|_| self.read_registers());
+        self.mute = mute_state;
+        let unmute = self.upload();
+        let muted = muted?;
+        unmute?;
+        delay.delay_ms(10);
+        let unmuted = self.read_registers()?;
+
+        Ok(muted.pll == before.pll
+            && unmuted.pll == before.pll
+            && (unmuted.level as i8 - before.level as i8).abs() <= 1)
+    }
+
+    // Tuning kept after standby and wake up, the device is woken up again when the check
+    // fails on the bus
+    fn test_standby<D>(&mut self, delay: &mut D) -> Result<bool, E>
+    where D: DelayMs<u16>
+    {
+        let before = self.read_registers()?;
+
+        let standby_state = self.standby;
+        self.standby = true;
+        let asleep = self.upload().and_then(|_| self.read_registers());
+        self.standby = standby_state;
+        let wake = self.upload();
+        let asleep = asleep?;
+        wake?;
+        delay.delay_ms(10);
+        let awake = self.read_registers()?;
+
+        Ok(asleep.pll == before.pll && awake.pll == before.pll)
+    }
+
+    // Hardware search from band edge towards it, band limit flag has to be reported. The edge
+    // may be outside the frequency range, which is widened to the band for the search.
+    fn search_band_limit<D>(&mut self, edge: f32, direction: SearchModeDirection, delay: &mut D)
+        -> Result<bool, E>
+    where D: DelayMs<u16>
+    {
+        let search_mode_dir = self.search_mode_dir;
+        let frequency_range = self.frequency_range;
+        self.frequency = edge;
+        self.frequency_range = band_range(self.band_limits);
+        self.search_mode_dir = direction;
+        self.search_mode = true;
+        let result = self.upload().and_then(|_| self.poll_band_limit(delay));
+        self.frequency_range = frequency_range;
+        self.search_mode_dir = search_mode_dir;
+        result
+    }
+
+    // Wait for the end of a hardware search, returns band limit flag
+    fn poll_band_limit<D>(&mut self, delay: &mut D) -> Result<bool, E>
+    where D: DelayMs<u16>
+    {
+        for _ in 0..SEARCH_POLLS {
+            let registers = self.read_registers()?;
+            if registers.ready {
+                return Ok(registers.band_limit);
+            }
+            delay.delay_ms(1);
+        }
+        Ok(false)
+    }
+}
@@ -0,0 +1,191 @@
+//! Tuner state change events
+//!
+//! `service` reads the device once and reports what changed since its last call to a
+//! `TunerObserver`, e.g. a closure or the `EventQueue` buffer. The first call reports the
+//! whole tuner state.
+
+use super::defs::*;
+use embedded_hal::blocking::i2c;
+
+/// Tuner state changes, frequencies in MHz
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TunerEvent {
+    /// Tuned frequency changed
+    Tuned(f32),
+    /// Reception switched between mono and stereo
+    StereoChanged(SoundMode),
+    /// ADC output level changed by more than the level deadband
+    LevelChanged(u8),
+    /// Band limit flag has been set by a search
+    BandLimitReached,
+    /// Search finished at frequency
+    SearchComplete(SearchStatus, f32),
+    /// Standby mode enabled or disabled
+    StandbyChanged(bool),
+}
+
+/// Receives tuner events
+pub trait TunerObserver {
+    fn on_event(&mut self, event: TunerEvent);
+}
+
+impl<F: FnMut(TunerEvent)> TunerObserver for F {
+    fn on_event(&mut self, event: TunerEvent) {
+        self(event)
+    }
+}
+
+/// Queue of up to N tuner events, further events are dropped
+#[derive(Debug)]
+pub struct EventQueue<const N: usize> {
+    events: [Option<TunerEvent>; N],
+    start: usize,
+    length: usize,
+    dropped: u32,
+}
+
+impl<const N: usize> EventQueue<N> {
+    /// Create empty queue
+    pub fn new() -> Self {
+        EventQueue {
+            events: [None; N],
+            start: 0,
+            length: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Take oldest event
+    pub fn pop(&mut self) -> Option<TunerEvent> {
+        if self.length == 0 {
+            return None;
+        }
+        let event = self.events[self.start].take();
+        self.start = (self.start + 1) % N;
+        self.length -= 1;
+        event
+    }
+
+    /// Number of queued events
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Number of events dropped on full queue
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+}
+
+impl<const N: usize> Default for EventQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> TunerObserver for EventQueue<N> {
+    fn on_event(&mut self, event: TunerEvent) {
+        if self.length == N {
+            self.dropped += 1;
+            return;
+        }
+        self.events[(self.start + self.length) % N] = Some(event);
+        self.length += 1;
+    }
+}
+
+// State last reported by service
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct EventState {
+    frequency: Option<f32>,
+    sound_mode: Option<SoundMode>,
+    level: Option<u8>,
+    band_limit: bool,
+    band_limit_reached: bool,
+    standby: Option<bool>,
+    search: Option<(SearchStatus, f32)>,
+    level_deadband: u8,
+}
+
+impl Default for EventState {
+    fn default() -> Self {
+        EventState {
+            frequency: None,
+            sound_mode: None,
+            level: None,
+            band_limit: false,
+            band_limit_reached: false,
+            standby: None,
+            search: None,
+            level_deadband: 1,
+        }
+    }
+}
+
+impl EventState {
+    // Search finished, reported by next service
+    pub(crate) fn search_complete(&mut self, status: SearchStatus, frequency: f32) {
+        self.search = Some((status, frequency));
+    }
+
+    // Search stopped at band limit, the flag itself is cleared by the following writes
+    pub(crate) fn band_limit_reached(&mut self) {
+        self.band_limit_reached = true;
+    }
+}
+
+impl<I2C, MODE> TEA5767<I2C, MODE> {
+    /// Set ADC level difference ignored by `LevelChanged`, default 1
+    pub fn set_level_deadband(&mut self, deadband: u8) {
+        self.events.level_deadband = deadband;
+    }
+}
+
+impl<I2C, E, MODE> TEA5767<I2C, MODE>
+where
+    I2C: i2c::Write<Error = E> + i2c::Read<Error = E>
+{
+    /// Read the device and report state changes since last call to observer. Reception is not
+    /// reported in standby
+    pub fn service<O: TunerObserver>(&mut self, observer: &mut O) -> Result<(), E> {
+        if let Some((status, frequency)) = self.events.search.take() {
+            observer.on_event(TunerEvent::SearchComplete(status, frequency));
+        }
+        if self.events.standby != Some(self.standby) {
+            self.events.standby = Some(self.standby);
+            observer.on_event(TunerEvent::StandbyChanged(self.standby));
+        }
+        if self.standby {
+            return Ok(());
+        }
+
+        let status = self.get_status()?;
+        let events = &mut self.events;
+
+        if events.frequency != Some(status.frequency) {
+            events.frequency = Some(status.frequency);
+            observer.on_event(TunerEvent::Tuned(status.frequency));
+        }
+        if events.sound_mode != Some(status.sound_mode) {
+            events.sound_mode = Some(status.sound_mode);
+            observer.on_event(TunerEvent::StereoChanged(status.sound_mode));
+        }
+        let deadband = events.level_deadband;
+        if events.level.map_or(true, |level| level.abs_diff(status.signal_level) > deadband) {
+            events.level = Some(status.signal_level);
+            observer.on_event(TunerEvent::LevelChanged(status.signal_level));
+        }
+        if (status.band_limit && !events.band_limit) || events.band_limit_reached {
+            observer.on_event(TunerEvent::BandLimitReached);
+        }
+        events.band_limit = status.band_limit;
+        events.band_limit_reached = false;
+        Ok(())
+    }
+}
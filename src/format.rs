@@ -0,0 +1,445 @@
+// defmt and ufmt formatting which can't be derived
+//
+// The driver holds the I2C bus, which is left out of its output. Register images are written
+// with datasheet bit names. ufmt has no floating point support, so frequencies are written
+// as MHz with at most two decimals.
+
+use super::defs::*;
+use super::regs::{ReadRegisters, WriteRegisters};
+
+#[cfg(feature = "defmt")]
+impl<I2C, MODE> defmt::Format for TEA5767<I2C, MODE> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "TEA5767 {{ address: {=u8:#x}, frequency: {=f32} MHz, band_limits: {}, standby: {=bool}, \
+             mute: {}, soft_mute: {=bool}, sound_mode: {}, high_cut_control: {=bool}, \
+             stereo_noise_canceling: {=bool}, crystal_frequency: {}, deemphasis_time: {} }}",
+            self.address,
+            self.frequency,
+            self.band_limits,
+            self.standby,
+            self.mute,
+            self.soft_mute,
+            self.sound_mode,
+            self.high_cut_control,
+            self.stereo_noise_canceling,
+            self.crystal_frequency,
+            self.deemphasis_time
+        )
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for WriteRegisters {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "W PLL={=u16} MUTE={=bool} SM={=bool} SUD={=bool} SSL={=u8} HLSI={=bool} MS={=bool} \
+             MR={=bool} ML={=bool} SWP1={=bool} SWP2={=bool} STBY={=bool} BL={=bool} XTAL={=bool} \
+             SMUTE={=bool} HCC={=bool} SNC={=bool} SI={=bool} PLLREF={=bool} DTC={=bool}",
+            self.pll,
+            self.mute,
+            self.search_mode,
+            self.search_up,
+            self.search_stop_level,
+            self.high_side_injection,
+            self.mono,
+            self.mute_right,
+            self.mute_left,
+            self.port1,
+            self.port2,
+            self.standby,
+            self.japanese_band,
+            self.xtal,
+            self.soft_mute,
+            self.high_cut_control,
+            self.stereo_noise_canceling,
+            self.search_indicator,
+            self.pllref,
+            self.deemphasis_75us
+        )
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for ReadRegisters {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "R RF={=bool} BLF={=bool} PLL={=u16} STEREO={=bool} IF={=u8:#x} LEV={=u8} CI={=u8}",
+            self.ready,
+            self.band_limit,
+            self.pll,
+            self.stereo,
+            self.if_counter,
+            self.level,
+            self.chip_id
+        )
+    }
+}
+
+#[cfg(feature = "ufmt")]
+mod ufmt_impls {
+    use super::*;
+    use crate::af::AfEvent;
+    use crate::channel::ChannelPlan;
+    use crate::command::{Command, Reply};
+    use crate::events::TunerEvent;
+    use crate::monitor::MonitorEvent;
+    use ufmt::{uDebug, uDisplay, uWrite, uwrite, Formatter};
+
+    // Frequency or spacing, MHz
+    struct Mhz(f32);
+
+    impl uDisplay for Mhz {
+        fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+            let hundredths = (self.0 * 100.0 + 0.5) as u32;
+            uwrite!(f, "{}", hundredths / 100)?;
+            match hundredths % 100 {
+                0 => Ok(()),
+                n if n % 10 == 0 => uwrite!(f, ".{}", n / 10),
+                n if n < 10 => uwrite!(f, ".0{}", n),
+                n => uwrite!(f, ".{}", n),
+            }
+        }
+    }
+
+    impl uDebug for Mhz {
+        fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+            uDisplay::fmt(self, f)
+        }
+    }
+
+    // List of frequencies, MHz
+    struct MhzList<'a>(&'a [f32]);
+
+    impl<'a> uDebug for MhzList<'a> {
+        fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+            f.debug_list()?.entries(self.0.iter().map(|&frequency| Mhz(frequency)))?.finish()
+        }
+    }
+
+    fn mhz_range(range: (f32, f32)) -> (Mhz, Mhz) {
+        (Mhz(range.0), Mhz(range.1))
+    }
+
+    impl<I2C, MODE> uDebug for TEA5767<I2C, MODE> {
+        fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+            f.debug_struct("TEA5767")?
+                .field("address", &self.address)?
+                .field("frequency", &Mhz(self.frequency))?
+                .field("band_limits", &self.band_limits)?
+                .field("standby", &self.standby)?
+                .field("mute", &self.mute)?
+                .field("soft_mute", &self.soft_mute)?
+                .field("sound_mode", &self.sound_mode)?
+                .field("high_cut_control", &self.high_cut_control)?
+                .field("stereo_noise_canceling", &self.stereo_noise_canceling)?
+                .field("crystal_frequency", &self.crystal_frequency)?
+                .field("deemphasis_time", &self.deemphasis_time)?
+                .finish()
+        }
+    }
+
+    impl uDebug for RegionProfile {
+        fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+            f.debug_struct("RegionProfile")?
+                .field("band_limits", &self.band_limits)?
+                .field("frequency_range", &mhz_range(self.frequency_range))?
+                .field("deemphasis_time", &self.deemphasis_time)?
+                .field("channel_spacing", &Mhz(self.channel_spacing))?
+                .field("seek_step", &Mhz(self.seek_step))?
+                .finish()
+        }
+    }
+
+    impl uDebug for ChannelPlan {
+        fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+            f.debug_struct("ChannelPlan")?
+                .field("numbering", &self.numbering())?
+                .field("frequency_range", &mhz_range(self.frequency_range()))?
+                .field("channel_spacing", &Mhz(self.channel_spacing()))?
+                .finish()
+        }
+    }
+
+    impl uDebug for TunerConfig {
+        fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+            f.debug_struct("TunerConfig")?
+                .field("band_limits", &self.band_limits)?
+                .field("frequency_range", &mhz_range(self.frequency_range))?
+                .field("channel_spacing", &Mhz(self.channel_spacing))?
+                .field("seek_step", &Mhz(self.seek_step))?
+                .field("deemphasis_time", &self.deemphasis_time)?
+                .field("sound_mode", &self.sound_mode)?
+                .field("mute", &self.mute)?
+                .field("soft_mute", &self.soft_mute)?
+                .field("high_cut_control", &self.high_cut_control)?
+                .field("stereo_noise_canceling", &self.stereo_noise_canceling)?
+                .field("crystal_frequency", &self.crystal_frequency)?
+                .field("injection_side", &self.injection_side)?
+                .field("search_adc_level", &self.search_adc_level)?
+                .field("search_mute_policy", &self.search_mute_policy)?
+                .finish()
+        }
+    }
+
+    impl uDebug for TunerStatus {
+        fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+            f.debug_struct("TunerStatus")?
+                .field("frequency", &Mhz(self.frequency))?
+                .field("sound_mode", &self.sound_mode)?
+                .field("signal_level", &self.signal_level)?
+                .field("ready", &self.ready)?
+                .field("band_limit", &self.band_limit)?
+                .finish()
+        }
+    }
+
+    impl uDebug for WriteRegisters {
+        fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+            uwrite!(
+                f,
+                "W PLL={} MUTE={} SM={} SUD={} SSL={} HLSI={} MS={} MR={} ML={} SWP1={} SWP2={} \
+                 STBY={} BL={} XTAL={} SMUTE={} HCC={} SNC={} SI={} PLLREF={} DTC={}",
+                self.pll,
+                self.mute as u8,
+                self.search_mode as u8,
+                self.search_up as u8,
+                self.search_stop_level,
+                self.high_side_injection as u8,
+                self.mono as u8,
+                self.mute_right as u8,
+                self.mute_left as u8,
+                self.port1 as u8,
+                self.port2 as u8,
+                self.standby as u8,
+                self.japanese_band as u8,
+                self.xtal as u8,
+                self.soft_mute as u8,
+                self.high_cut_control as u8,
+                self.stereo_noise_canceling as u8,
+                self.search_indicator as u8,
+                self.pllref as u8,
+                self.deemphasis_75us as u8
+            )
+        }
+    }
+
+    impl uDebug for ReadRegisters {
+        fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+            uwrite!(
+                f,
+                "R RF={} BLF={} PLL={} STEREO={} IF={} LEV={} CI={}",
+                self.ready as u8,
+                self.band_limit as u8,
+                self.pll,
+                self.stereo as u8,
+                self.if_counter,
+                self.level,
+                self.chip_id
+            )
+        }
+    }
+
+    impl uDebug for Command {
+        fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+            match self {
+                Command::Tune(frequency) => f.debug_tuple("Tune")?.field(&Mhz(*frequency))?.finish(),
+                Command::Seek(direction, level) => {
+                    f.debug_tuple("Seek")?.field(direction)?.field(level)?.finish()
+                }
+                Command::Scan(level) => f.debug_tuple("Scan")?.field(level)?.finish(),
+                Command::Status => f.write_str("Status"),
+                Command::Mute(channel) => f.debug_tuple("Mute")?.field(channel)?.finish(),
+                Command::Unmute(channel) => f.debug_tuple("Unmute")?.field(channel)?.finish(),
+                Command::SoundMode(mode) => f.debug_tuple("SoundMode")?.field(mode)?.finish(),
+                Command::Standby(standby) => f.debug_tuple("Standby")?.field(standby)?.finish(),
+            }
+        }
+    }
+
+    impl uDebug for Reply {
+        fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+            match self {
+                Reply::Ok => f.write_str("Ok"),
+                Reply::Tuned(frequency) => f.debug_tuple("Tuned")?.field(&Mhz(*frequency))?.finish(),
+                Reply::Seek(status, frequency) => {
+                    f.debug_tuple("Seek")?.field(status)?.field(&Mhz(*frequency))?.finish()
+                }
+                Reply::Scan { stations, count } => f
+                    .debug_struct("Scan")?
+                    .field("stations", &MhzList(&stations[..*count]))?
+                    .finish(),
+                Reply::Status(status) => f.debug_tuple("Status")?.field(status)?.finish(),
+                Reply::Error(error) => f.debug_tuple("Error")?.field(error)?.finish(),
+                Reply::DeviceError => f.write_str("DeviceError"),
+            }
+        }
+    }
+
+    impl uDebug for MonitorEvent {
+        fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+            match self {
+                MonitorEvent::SignalWeak(frequency) => {
+                    f.debug_tuple("SignalWeak")?.field(&Mhz(*frequency))?.finish()
+                }
+                MonitorEvent::SignalRecovered(frequency) => {
+                    f.debug_tuple("SignalRecovered")?.field(&Mhz(*frequency))?.finish()
+                }
+                MonitorEvent::Retuned { lost, frequency } => f
+                    .debug_struct("Retuned")?
+                    .field("lost", &Mhz(*lost))?
+                    .field("frequency", &Mhz(*frequency))?
+                    .finish(),
+                MonitorEvent::NoAlternative(frequency) => {
+                    f.debug_tuple("NoAlternative")?.field(&Mhz(*frequency))?.finish()
+                }
+            }
+        }
+    }
+
+    impl uDebug for AfEvent {
+        fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+            match self {
+                AfEvent::Measured { frequency, level } => f
+                    .debug_struct("Measured")?
+                    .field("frequency", &Mhz(*frequency))?
+                    .field("level", level)?
+                    .finish(),
+                AfEvent::Switched { from, to } => f
+                    .debug_struct("Switched")?
+                    .field("from", &Mhz(*from))?
+                    .field("to", &Mhz(*to))?
+                    .finish(),
+            }
+        }
+    }
+
+    impl uDebug for TunerEvent {
+        fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+            match self {
+                TunerEvent::Tuned(frequency) => {
+                    f.debug_tuple("Tuned")?.field(&Mhz(*frequency))?.finish()
+                }
+                TunerEvent::StereoChanged(mode) => {
+                    f.debug_tuple("StereoChanged")?.field(mode)?.finish()
+                }
+                TunerEvent::LevelChanged(level) => {
+                    f.debug_tuple("LevelChanged")?.field(level)?.finish()
+                }
+                TunerEvent::BandLimitReached => f.write_str("BandLimitReached"),
+                TunerEvent::SearchComplete(status, frequency) => {
+                    f.debug_tuple("SearchComplete")?
+                        .field(status)?
+                        .field(&Mhz(*frequency))?
+                        .finish()
+                }
+                TunerEvent::StandbyChanged(standby) => {
+                    f.debug_tuple("StandbyChanged")?.field(standby)?.finish()
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "sim")]
+    impl uDebug for crate::sim::Station {
+        fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+            f.debug_struct("Station")?
+                .field("frequency", &Mhz(self.frequency))?
+                .field("level", &self.level)?
+                .field("stereo", &self.stereo)?
+                .finish()
+        }
+    }
+
+    #[cfg(feature = "presets")]
+    impl uDebug for crate::presets::Presets {
+        fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+            let mut stations = [0.0; crate::presets::PRESETS_SLOTS];
+            let mut count = 0;
+            for frequency in (0..stations.len()).filter_map(|slot| self.get(slot)) {
+                stations[count] = frequency;
+                count += 1;
+            }
+            f.debug_struct("Presets")?
+                .field("config", &self.config)?
+                .field("frequency", &Mhz(self.frequency))?
+                .field("stations", &MhzList(&stations[..count]))?
+                .finish()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        struct Buffer {
+            bytes: [u8; 256],
+            length: usize,
+        }
+
+        impl uWrite for Buffer {
+            type Error = ();
+
+            fn write_str(&mut self, s: &str) -> Result<(), ()> {
+                let end = self.length + s.len();
+                self.bytes.get_mut(self.length..end).ok_or(())?.copy_from_slice(s.as_bytes());
+                self.length = end;
+                Ok(())
+            }
+        }
+
+        fn format(value: &impl uDebug) -> Buffer {
+            let mut buffer = Buffer { bytes: [0; 256], length: 0 };
+            ufmt::uwrite!(&mut buffer, "{:?}", value).unwrap();
+            buffer
+        }
+
+        #[test]
+        fn test_ufmt_frequency() {
+            let status = TunerStatus {
+                frequency: 101.1,
+                sound_mode: SoundMode::Stereo,
+                signal_level: 12,
+                ready: true,
+                band_limit: false,
+            };
+            let buffer = format(&status);
+            assert_eq!(&buffer.bytes[..buffer.length], &b"TunerStatus { frequency: 101.1, \
+                sound_mode: Stereo, signal_level: 12, ready: true, band_limit: false }"[..]);
+        }
+
+        #[test]
+        fn test_ufmt_monitor_event() {
+            let buffer = format(&MonitorEvent::Retuned { lost: 95.1, frequency: 101.3 });
+            assert_eq!(&buffer.bytes[..buffer.length],
+                       &b"Retuned { lost: 95.1, frequency: 101.3 }"[..]);
+        }
+
+        #[test]
+        fn test_ufmt_tuner_event() {
+            let buffer = format(&TunerEvent::SearchComplete(SearchStatus::Success, 88.05));
+            assert_eq!(&buffer.bytes[..buffer.length], &b"SearchComplete(Success, 88.05)"[..]);
+        }
+
+        #[test]
+        fn test_ufmt_channel_plan() {
+            use crate::channel::ChannelNumbering;
+
+            let plan = ChannelPlan::new(ChannelNumbering::Fcc, BandLimits::EuropeUS, 0.2).unwrap();
+            let buffer = format(&plan);
+            assert_eq!(&buffer.bytes[..buffer.length], &b"ChannelPlan { numbering: Fcc, \
+                frequency_range: (87.5, 108), channel_spacing: 0.2 }"[..]);
+        }
+
+        #[test]
+        fn test_ufmt_read_registers() {
+            let registers = ReadRegisters::from_bytes([0b1010_1010, 0b1111_1001, 0b1011_0111, 0b1101_0000, 0]);
+            let buffer = format(&registers);
+            assert_eq!(&buffer.bytes[..buffer.length],
+                       &b"R RF=1 BLF=0 PLL=11001 STEREO=1 IF=55 LEV=13 CI=0"[..]);
+        }
+    }
+}
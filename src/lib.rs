@@ -37,9 +37,9 @@
 //! Licensed under either of
 //!
 //! * Apache License, Version 2.0 ([LICENSE-APACHE](LICENSE-APACHE) or
-//! http://www.apache.org/licenses/LICENSE-2.0)
+//!   http://www.apache.org/licenses/LICENSE-2.0)
 //! * MIT license ([LICENSE-MIT](LICENSE-MIT) or
-//! http://opensource.org/licenses/MIT)
+//!   http://opensource.org/licenses/MIT)
 //!
 //! at your option.
 //!
@@ -63,6 +63,25 @@
 //#![allow(unused)] // 1st stage of development
 //#![allow(dead_code)] // 1st stage of development
 
-mod regs;
+pub mod regs;
 mod device;
-pub mod defs;
\ No newline at end of file
+pub mod defs;
+pub mod tuner;
+pub mod mux;
+pub mod bus;
+pub mod command;
+#[cfg(feature = "sim")]
+pub mod sim;
+pub mod monitor;
+pub mod af;
+pub mod diagnostics;
+pub mod retry;
+pub mod trace;
+pub mod events;
+pub mod profile;
+pub mod channel;
+pub mod step;
+#[cfg(feature = "presets")]
+pub mod presets;
+#[cfg(any(feature = "defmt", feature = "ufmt"))]
+mod format;
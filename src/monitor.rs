@@ -0,0 +1,126 @@
+//! Station loss detection
+//!
+//! The monitor is called periodically with a time stamp from any millisecond clock. It measures
+//! the ADC level and IF counter of the tuned station and, once the signal stays below threshold
+//! for the configured time, tunes to the strongest alternative station of the frequency range.
+
+use super::defs::*;
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::blocking::i2c;
+
+/// Station loss monitor configuration
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MonitorConfig {
+    /// minimum ADC output level of the tuned station, 0 - 15
+    pub adc_level: u8,
+    /// accepted IF counter range (min, max) of the tuned station
+    pub if_counter: (u8, u8),
+    /// time the signal has to stay weak before the station is lost, ms
+    pub loss_time_ms: u32,
+    /// criteria an alternative station has to meet
+    pub criteria: SearchCriteria,
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        MonitorConfig {
+            adc_level: 5,
+            if_counter: IF_COUNTER_TUNED,
+            loss_time_ms: 3000,
+            criteria: SearchCriteria::default(),
+        }
+    }
+}
+
+/// Events reported by the monitor, frequencies in MHz
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MonitorEvent {
+    /// Signal of the tuned station dropped below threshold
+    SignalWeak(f32),
+    /// Signal recovered before the station was lost
+    SignalRecovered(f32),
+    /// Station lost, tuned to the strongest alternative station
+    Retuned { lost: f32, frequency: f32 },
+    /// Station lost, no alternative station meets criteria and tuned frequency is kept
+    NoAlternative(f32),
+}
+
+/// Station loss monitor
+#[derive(Debug)]
+pub struct StationMonitor {
+    config: MonitorConfig,
+    weak_since: Option<u32>,
+}
+
+impl StationMonitor {
+    /// Create new monitor
+    pub fn new(config: MonitorConfig) -> Self {
+        StationMonitor {
+            config,
+            weak_since: None,
+        }
+    }
+
+    /// Get monitor configuration
+    pub fn config(&self) -> MonitorConfig {
+        self.config
+    }
+
+    /// Signal of the tuned station is below threshold
+    pub fn is_weak(&self) -> bool {
+        self.weak_since.is_some()
+    }
+
+    /// Forget measured state, call after tuning another station
+    pub fn reset(&mut self) {
+        self.weak_since = None;
+    }
+
+    /// Measure the tuned station, re-seek when it has been weak for the loss time
+    pub fn check<I2C, E, MODE, D>(&mut self, tuner: &mut TEA5767<I2C, MODE>, now_ms: u32,
+                                  delay: &mut D) -> Result<Option<MonitorEvent>, E>
+    where
+        I2C: i2c::Write<Error = E> + i2c::Read<Error = E>,
+        MODE: Tunable,
+        D: DelayMs<u16>,
+    {
+        let registers = tuner.read_registers()?;
+        let frequency = tuner.frequency;
+        let weak = registers.level < self.config.adc_level
+            || registers.if_counter < self.config.if_counter.0
+            || registers.if_counter > self.config.if_counter.1;
+
+        let event = match (weak, self.weak_since) {
+            (false, None) => None,
+            (false, Some(_)) => {
+                self.weak_since = None;
+                Some(MonitorEvent::SignalRecovered(frequency))
+            }
+            (true, None) => {
+                self.weak_since = Some(now_ms);
+                Some(MonitorEvent::SignalWeak(frequency))
+            }
+            (true, Some(since)) if now_ms.wrapping_sub(since) < self.config.loss_time_ms => None,
+            (true, Some(_)) => {
+                self.weak_since = None;
+                let status = tuner.search_strongest(self.config.criteria, delay)?;
+                match status {
+                    SearchStatus::Success if tuner.frequency == frequency => {
+                        Some(MonitorEvent::SignalRecovered(frequency))
+                    }
+                    SearchStatus::Success => Some(MonitorEvent::Retuned {
+                        lost: frequency,
+                        frequency: tuner.frequency,
+                    }),
+                    SearchStatus::Failure => Some(MonitorEvent::NoAlternative(frequency)),
+                }
+            }
+        };
+        Ok(event)
+    }
+}
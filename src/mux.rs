@@ -0,0 +1,87 @@
+use core::cell::{Cell, RefCell};
+use embedded_hal::blocking::i2c;
+
+/// TCA9548A default address, A0 - A2 pins low
+pub const TCA9548A_ADDRESS: u8 = 0x70;
+
+/// TCA9548A I2C multiplexer, owns the physical bus and hands out one I2C per channel,
+/// so several TEA5767 instances can be driven on one bus
+#[derive(Debug)]
+pub struct Tca9548a<I2C> {
+    bus: RefCell<I2C>,
+    address: u8,
+    selected: Cell<Option<u8>>,
+}
+
+/// I2C behind a multiplexer channel, the channel is selected before each transaction
+#[derive(Debug)]
+pub struct MuxChannel<'a, I2C> {
+    mux: &'a Tca9548a<I2C>,
+    channel: u8,
+}
+
+impl<I2C, E> Tca9548a<I2C>
+where
+    I2C: i2c::Write<Error = E>
+{
+    /// Create new multiplexer instance at address 0x70 - 0x77
+    pub fn new(i2c: I2C, address: u8) -> Self {
+        Tca9548a {
+            bus: RefCell::new(i2c),
+            address,
+            selected: Cell::new(None),
+        }
+    }
+
+    /// Get I2C of channel 0 - 7, None for a channel the multiplexer does not have
+    pub fn channel(&self, channel: u8) -> Option<MuxChannel<'_, I2C>> {
+        if channel > 7 {
+            return None;
+        }
+        Some(MuxChannel {
+            mux: self,
+            channel,
+        })
+    }
+
+    /// Remove multiplexer instance
+    pub fn destroy(self) -> I2C {
+        self.bus.into_inner()
+    }
+
+    // Select channel unless already selected
+    fn select(&self, bus: &mut I2C, channel: u8) -> Result<(), E> {
+        if self.selected.get() != Some(channel) {
+            self.selected.set(None);
+            bus.write(self.address, &[1 << channel])?;
+            self.selected.set(Some(channel));
+        }
+        Ok(())
+    }
+}
+
+impl<'a, I2C, E> i2c::Write for MuxChannel<'a, I2C>
+where
+    I2C: i2c::Write<Error = E>
+{
+    type Error = E;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), E> {
+        let mut bus = self.mux.bus.borrow_mut();
+        self.mux.select(&mut bus, self.channel)?;
+        bus.write(address, bytes)
+    }
+}
+
+impl<'a, I2C, E> i2c::Read for MuxChannel<'a, I2C>
+where
+    I2C: i2c::Write<Error = E> + i2c::Read<Error = E>
+{
+    type Error = E;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), E> {
+        let mut bus = self.mux.bus.borrow_mut();
+        self.mux.select(&mut bus, self.channel)?;
+        bus.read(address, buffer)
+    }
+}
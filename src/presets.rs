@@ -0,0 +1,483 @@
+use super::defs::*;
+use super::device::band_range;
+use embedded_hal::blocking::i2c;
+use embedded_storage::{ReadStorage, Storage};
+use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+#[allow(unused_imports)] // float methods are inherent with std
+use micromath::F32Ext;
+
+/// Number of station preset slots
+pub const PRESETS_SLOTS: usize = 30;
+/// Size of stored presets, bytes
+pub const PRESETS_SIZE: usize = 80;
+/// Layout version of stored presets
+pub const PRESETS_VERSION: u8 = 2;
+
+// Stored presets layout, multi-byte values are little endian,
+// frequencies are in 10 kHz units:
+//  0..2   magic "T5"
+//  2      layout version
+//  3      band, sound mode, soft mute, HCC, SNC, de-emphasis, injection side
+//  4      mute channel, crystal, search ADC level, search mute policy
+//  5..9   frequency range
+//  9..11  channel spacing
+//  11..13 seek step
+//  13..15 last tuned frequency
+//  15..17 crystal frequency offset, signed, 0.1 ppm units
+//  17     number of slots
+//  18..78 slots, 0 = empty
+//  78..80 CRC-16/CCITT-FALSE of bytes 0..78
+const MAGIC: [u8; 2] = *b"T5";
+const SLOTS_COUNT_OFFSET: usize = 17;
+const SLOTS_OFFSET: usize = 18;
+const CRC_OFFSET: usize = PRESETS_SIZE - 2;
+
+// NOR flash reads and writes are padded to READ_SIZE/WRITE_SIZE in this buffer
+const NOR_BUFFER_SIZE: usize = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum PresetsError<E> {
+    /// Storage access failed
+    Storage(E),
+    /// Nothing stored at offset
+    Empty,
+    /// Stored with unsupported layout version
+    Version(u8),
+    /// Stored data corrupted
+    Crc,
+    /// Stored value out of range
+    Invalid,
+    /// Offset or access size does not match flash alignment
+    NotAligned,
+}
+
+/// Station presets with configuration and last tuned frequency
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Presets {
+    /// configuration restored on boot
+    pub config: TunerConfig,
+    /// last tuned frequency, MHz
+    pub frequency: f32,
+    /// crystal frequency offset found by `calibrate_crystal`, ppm
+    pub crystal_ppm: f32,
+    stations: [u16; PRESETS_SLOTS],
+}
+
+impl Presets {
+    /// Create presets with all slots empty and no crystal frequency offset
+    pub fn new(config: TunerConfig, frequency: f32) -> Self {
+        Presets {
+            config,
+            frequency,
+            crystal_ppm: 0.0,
+            stations: [0; PRESETS_SLOTS],
+        }
+    }
+
+    /// Get station frequency of slot, None for an empty slot
+    pub fn get(&self, slot: usize) -> Option<f32> {
+        match self.stations.get(slot) {
+            Some(&station) if station != 0 => Some(from_10khz(station)),
+            _ => None,
+        }
+    }
+
+    /// Store station frequency in slot, returns false if slot does not exist or frequency is
+    /// outside the band
+    pub fn set(&mut self, slot: usize, frequency: f32) -> bool {
+        let (low, high) = band_range(self.config.band_limits);
+        if !(frequency >= low && frequency <= high) {
+            return false;
+        }
+        match self.stations.get_mut(slot) {
+            Some(station) => {
+                *station = to_10khz(frequency);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Empty slot
+    pub fn clear(&mut self, slot: usize) {
+        if let Some(station) = self.stations.get_mut(slot) {
+            *station = 0;
+        }
+    }
+
+    /// Encode presets to stored layout
+    pub fn to_bytes(&self) -> [u8; PRESETS_SIZE] {
+        let mut bytes = [0_u8; PRESETS_SIZE];
+        let config = &self.config;
+
+        bytes[0..2].copy_from_slice(&MAGIC);
+        bytes[2] = PRESETS_VERSION;
+
+        bytes[3] = (config.band_limits == BandLimits::Japanese) as u8
+            | ((config.sound_mode == SoundMode::Mono) as u8) << 1
+            | (config.soft_mute as u8) << 2
+            | (config.high_cut_control as u8) << 3
+            | (config.stereo_noise_canceling as u8) << 4
+            | ((config.deemphasis_time == DeemphasisTime::Dtc75) as u8) << 5
+            | ((config.injection_side == InjectionSide::LowSide) as u8) << 6;
+
+        let mute = match config.mute {
+            MuteChannel::None => 0,
+            MuteChannel::Left => 1,
+            MuteChannel::Right => 2,
+            MuteChannel::Both => 3,
+        };
+        let crystal = match config.crystal_frequency {
+            CrystalFrequency::Clk32_768Khz => 0,
+            CrystalFrequency::Clk13Mhz => 1,
+            CrystalFrequency::Clk6_5MHz => 2,
+        };
+        let search_adc_level = match config.search_adc_level {
+            SearchAdcLevel::Low => 1,
+            SearchAdcLevel::Mid => 2,
+            SearchAdcLevel::High => 3,
+        };
+        let search_mute_policy = match config.search_mute_policy {
+            SearchMutePolicy::Mute => 0,
+            SearchMutePolicy::SoftMute => 1,
+            SearchMutePolicy::None => 2,
+        };
+        bytes[4] = mute | crystal << 2 | search_adc_level << 4 | search_mute_policy << 6;
+
+        bytes[5..7].copy_from_slice(&to_10khz(config.frequency_range.0).to_le_bytes());
+        bytes[7..9].copy_from_slice(&to_10khz(config.frequency_range.1).to_le_bytes());
+        bytes[9..11].copy_from_slice(&to_10khz(config.channel_spacing).to_le_bytes());
+        bytes[11..13].copy_from_slice(&to_10khz(config.seek_step).to_le_bytes());
+        bytes[13..15].copy_from_slice(&to_10khz(self.frequency).to_le_bytes());
+        let crystal_ppm = (self.crystal_ppm * 10.0).round() as i16;
+        bytes[15..17].copy_from_slice(&crystal_ppm.to_le_bytes());
+
+        bytes[SLOTS_COUNT_OFFSET] = PRESETS_SLOTS as u8;
+        for (slot, station) in self.stations.iter().enumerate() {
+            let offset = SLOTS_OFFSET + 2 * slot;
+            bytes[offset..offset + 2].copy_from_slice(&station.to_le_bytes());
+        }
+
+        let crc = crc16(&bytes[..CRC_OFFSET]);
+        bytes[CRC_OFFSET..].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+
+    /// Decode presets from stored layout
+    pub fn from_bytes(bytes: &[u8; PRESETS_SIZE]) -> Result<Self, PresetsError<()>> {
+        decode(bytes)
+    }
+
+    /// Read presets from EEPROM like storage at offset
+    pub fn load<S>(storage: &mut S, offset: u32) -> Result<Self, PresetsError<S::Error>>
+    where S: ReadStorage
+    {
+        let mut bytes = [0_u8; PRESETS_SIZE];
+        storage.read(offset, &mut bytes).map_err(PresetsError::Storage)?;
+        decode(&bytes)
+    }
+
+    /// Write presets to EEPROM like storage at offset
+    pub fn save<S>(&self, storage: &mut S, offset: u32) -> Result<(), PresetsError<S::Error>>
+    where S: Storage
+    {
+        storage.write(offset, &self.to_bytes()).map_err(PresetsError::Storage)
+    }
+
+    /// Read presets from NOR flash at offset aligned to READ_SIZE
+    pub fn load_nor_flash<F>(flash: &mut F, offset: u32) -> Result<Self, PresetsError<F::Error>>
+    where F: ReadNorFlash
+    {
+        let length = align_up(PRESETS_SIZE, F::READ_SIZE);
+        if offset as usize % F::READ_SIZE != 0 || length > NOR_BUFFER_SIZE {
+            return Err(PresetsError::NotAligned);
+        }
+
+        let mut buffer = [0_u8; NOR_BUFFER_SIZE];
+        flash.read(offset, &mut buffer[..length]).map_err(PresetsError::Storage)?;
+
+        let mut bytes = [0_u8; PRESETS_SIZE];
+        bytes.copy_from_slice(&buffer[..PRESETS_SIZE]);
+        decode(&bytes)
+    }
+
+    /// Erase flash and write presets at offset aligned to ERASE_SIZE
+    pub fn save_nor_flash<F>(&self, flash: &mut F, offset: u32) -> Result<(), PresetsError<F::Error>>
+    where F: NorFlash
+    {
+        let length = align_up(PRESETS_SIZE, F::WRITE_SIZE);
+        if offset as usize % F::ERASE_SIZE != 0 || length > NOR_BUFFER_SIZE {
+            return Err(PresetsError::NotAligned);
+        }
+
+        let mut buffer = [0xFF_u8; NOR_BUFFER_SIZE];
+        buffer[..PRESETS_SIZE].copy_from_slice(&self.to_bytes());
+
+        let erase_end = offset + align_up(PRESETS_SIZE, F::ERASE_SIZE) as u32;
+        flash.erase(offset, erase_end).map_err(PresetsError::Storage)?;
+        flash.write(offset, &buffer[..length]).map_err(PresetsError::Storage)
+    }
+}
+
+impl<I2C, E, MODE: Tunable> TEA5767<I2C, MODE>
+where
+    I2C: i2c::Write<Error = E> + i2c::Read<Error = E>
+{
+    /// Restore configuration, crystal frequency offset and last tuned frequency with a single
+    /// register write
+    pub fn restore(&mut self, presets: &Presets) -> Result<(), ConfigError<E>> {
+        if !presets.config.is_valid() {
+            return Err(ConfigError::Invalid);
+        }
+        self.frequency = presets.frequency;
+        self.crystal_ppm = presets.crystal_ppm;
+        self.set_config(&presets.config)
+    }
+}
+
+// decode presets, storage error type is chosen by caller
+fn decode<E>(bytes: &[u8; PRESETS_SIZE]) -> Result<Presets, PresetsError<E>> {
+    if bytes[0..2] != MAGIC {
+        return Err(PresetsError::Empty);
+    }
+    if bytes[2] != PRESETS_VERSION {
+        return Err(PresetsError::Version(bytes[2]));
+    }
+    if crc16(&bytes[..CRC_OFFSET]) != read_u16(bytes, CRC_OFFSET) {
+        return Err(PresetsError::Crc);
+    }
+    if bytes[SLOTS_COUNT_OFFSET] as usize != PRESETS_SLOTS {
+        return Err(PresetsError::Invalid);
+    }
+
+    let mute = match bytes[4] & 0b11 {
+        0 => MuteChannel::None,
+        1 => MuteChannel::Left,
+        2 => MuteChannel::Right,
+        _ => MuteChannel::Both,
+    };
+    let crystal_frequency = match (bytes[4] >> 2) & 0b11 {
+        0 => CrystalFrequency::Clk32_768Khz,
+        1 => CrystalFrequency::Clk13Mhz,
+        2 => CrystalFrequency::Clk6_5MHz,
+        _ => return Err(PresetsError::Invalid),
+    };
+    let search_adc_level = match (bytes[4] >> 4) & 0b11 {
+        1 => SearchAdcLevel::Low,
+        2 => SearchAdcLevel::Mid,
+        3 => SearchAdcLevel::High,
+        _ => return Err(PresetsError::Invalid),
+    };
+    let search_mute_policy = match (bytes[4] >> 6) & 0b11 {
+        0 => SearchMutePolicy::Mute,
+        1 => SearchMutePolicy::SoftMute,
+        2 => SearchMutePolicy::None,
+        _ => return Err(PresetsError::Invalid),
+    };
+
+    let flags = bytes[3];
+    let config = TunerConfig {
+        band_limits: if flags & 1 != 0 { BandLimits::Japanese } else { BandLimits::EuropeUS },
+        frequency_range: (from_10khz(read_u16(bytes, 5)), from_10khz(read_u16(bytes, 7))),
+        channel_spacing: from_10khz(read_u16(bytes, 9)),
+        seek_step: from_10khz(read_u16(bytes, 11)),
+        deemphasis_time: if flags & 1 << 5 != 0 { DeemphasisTime::Dtc75 } else { DeemphasisTime::Dtc50 },
+        sound_mode: if flags & 1 << 1 != 0 { SoundMode::Mono } else { SoundMode::Stereo },
+        mute,
+        soft_mute: flags & 1 << 2 != 0,
+        high_cut_control: flags & 1 << 3 != 0,
+        stereo_noise_canceling: flags & 1 << 4 != 0,
+        crystal_frequency,
+        injection_side: if flags & 1 << 6 != 0 { InjectionSide::LowSide } else { InjectionSide::HighSide },
+        search_adc_level,
+        search_mute_policy,
+    };
+    if !config.is_valid() {
+        return Err(PresetsError::Invalid);
+    }
+
+    let mut presets = Presets::new(config, from_10khz(read_u16(bytes, 13)));
+    presets.crystal_ppm = read_u16(bytes, 15) as i16 as f32 / 10.0;
+    for (slot, station) in presets.stations.iter_mut().enumerate() {
+        *station = read_u16(bytes, SLOTS_OFFSET + 2 * slot);
+    }
+    Ok(presets)
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn to_10khz(frequency: f32) -> u16 {
+    (frequency * 100.0).round() as u16
+}
+
+fn from_10khz(value: u16) -> f32 {
+    value as f32 / 100.0
+}
+
+fn align_up(length: usize, alignment: usize) -> usize {
+    (length + alignment - 1) / alignment * alignment
+}
+
+// CRC-16/CCITT-FALSE
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFF_u16;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { crc << 1 ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::presets::*;
+    use embedded_storage::nor_flash::{ErrorType, NorFlashErrorKind};
+
+    struct Eeprom([u8; 256]);
+
+    impl ReadStorage for Eeprom {
+        type Error = ();
+
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), ()> {
+            let offset = offset as usize;
+            bytes.copy_from_slice(&self.0[offset..offset + bytes.len()]);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            self.0.len()
+        }
+    }
+
+    impl Storage for Eeprom {
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), ()> {
+            let offset = offset as usize;
+            self.0[offset..offset + bytes.len()].copy_from_slice(bytes);
+            Ok(())
+        }
+    }
+
+    struct Flash([u8; 512]);
+
+    impl ErrorType for Flash {
+        type Error = NorFlashErrorKind;
+    }
+
+    impl ReadNorFlash for Flash {
+        const READ_SIZE: usize = 4;
+
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), NorFlashErrorKind> {
+            if offset % 4 != 0 || bytes.len() % 4 != 0 {
+                return Err(NorFlashErrorKind::NotAligned);
+            }
+            let offset = offset as usize;
+            bytes.copy_from_slice(&self.0[offset..offset + bytes.len()]);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            self.0.len()
+        }
+    }
+
+    impl NorFlash for Flash {
+        const WRITE_SIZE: usize = 8;
+        const ERASE_SIZE: usize = 256;
+
+        fn erase(&mut self, from: u32, to: u32) -> Result<(), NorFlashErrorKind> {
+            self.0[from as usize..to as usize].iter_mut().for_each(|b| *b = 0xFF);
+            Ok(())
+        }
+
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), NorFlashErrorKind> {
+            if offset % 8 != 0 || bytes.len() % 8 != 0 {
+                return Err(NorFlashErrorKind::NotAligned);
+            }
+            let offset = offset as usize;
+            for (cell, byte) in self.0[offset..offset + bytes.len()].iter_mut().zip(bytes) {
+                *cell &= *byte;
+            }
+            Ok(())
+        }
+    }
+
+    fn presets() -> Presets {
+        let config = TunerConfig {
+            band_limits: BandLimits::Japanese,
+            frequency_range: (76.0, 90.0),
+            deemphasis_time: DeemphasisTime::Dtc50,
+            mute: MuteChannel::Left,
+            crystal_frequency: CrystalFrequency::Clk13Mhz,
+            search_adc_level: SearchAdcLevel::High,
+            search_mute_policy: SearchMutePolicy::SoftMute,
+            ..Default::default()
+        };
+        let mut presets = Presets::new(config, 80.7);
+        presets.crystal_ppm = -41.3;
+        presets.set(0, 76.5);
+        presets.set(29, 89.9);
+        presets
+    }
+
+    #[test]
+    fn test_presets_bytes() {
+        let presets = presets();
+        assert_eq!(Presets::from_bytes(&presets.to_bytes()), Ok(presets));
+        assert_eq!(presets.get(0), Some(76.5));
+        assert_eq!(presets.get(1), None);
+        assert_eq!(presets.get(30), None);
+    }
+
+    #[test]
+    fn test_presets_wide_steps() {
+        let mut presets = presets();
+        presets.config.channel_spacing = 3.0;
+        presets.config.seek_step = 2.6;
+        assert!(presets.config.is_valid());
+        assert_eq!(Presets::from_bytes(&presets.to_bytes()), Ok(presets));
+    }
+
+    #[test]
+    fn test_presets_set_out_of_band() {
+        let mut presets = presets();
+        assert!(!presets.set(1, f32::NAN));
+        assert!(!presets.set(1, -1.0));
+        assert!(!presets.set(1, 95.1));
+        assert_eq!(presets.get(1), None);
+        assert!(presets.set(1, 91.0));
+    }
+
+    #[test]
+    fn test_presets_corrupted() {
+        let mut bytes = presets().to_bytes();
+        bytes[20] ^= 0x01;
+        assert_eq!(Presets::from_bytes(&bytes), Err(PresetsError::Crc));
+        bytes[2] = PRESETS_VERSION + 1;
+        assert_eq!(Presets::from_bytes(&bytes), Err(PresetsError::Version(PRESETS_VERSION + 1)));
+        assert_eq!(Presets::from_bytes(&[0xFF; PRESETS_SIZE]), Err(PresetsError::Empty));
+    }
+
+    #[test]
+    fn test_presets_storage() {
+        let mut eeprom = Eeprom([0xFF; 256]);
+        assert_eq!(Presets::load(&mut eeprom, 16), Err(PresetsError::Empty));
+        presets().save(&mut eeprom, 16).unwrap();
+        assert_eq!(Presets::load(&mut eeprom, 16), Ok(presets()));
+    }
+
+    #[test]
+    fn test_presets_nor_flash() {
+        let mut flash = Flash([0x00; 512]);
+        assert_eq!(presets().save_nor_flash(&mut flash, 16), Err(PresetsError::NotAligned));
+        presets().save_nor_flash(&mut flash, 256).unwrap();
+        assert_eq!(Presets::load_nor_flash(&mut flash, 256), Ok(presets()));
+    }
+}
@@ -0,0 +1,182 @@
+//! Listening profiles
+//!
+//! A profile combines high cut control, stereo noise canceling, soft mute and optionally sound
+//! mode for a listening situation and is written to the device at once. Only `Speech` of the
+//! built-in profiles sets the sound mode. The sound mode chosen by the user is kept while a
+//! profile forces one and restored by the next profile without sound mode. `AdaptiveProfile`
+//! switches between a profile for weak and one for strong reception based on measured ADC
+//! level.
+
+use super::defs::*;
+use embedded_hal::blocking::i2c;
+
+/// Reception settings of a listening profile
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProfileSettings {
+    pub high_cut_control: bool,
+    pub stereo_noise_canceling: bool,
+    pub soft_mute: bool,
+    /// sound mode forced by the profile, None applies the sound mode chosen by the user
+    pub sound_mode: Option<SoundMode>,
+}
+
+/// Listening profiles
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ListeningProfile {
+    /// Settings of `TEA5767::new`: high cut and stereo noise canceling
+    Default,
+    /// Weak or fading stations: everything reducing noise, soft mute included
+    WeakSignal,
+    /// Strong local stations: full audio bandwidth and stereo separation
+    HiFi,
+    /// Talk radio: noise reduction, switches to mono
+    Speech,
+    Custom(ProfileSettings),
+}
+
+impl ListeningProfile {
+    /// Get settings applied by the profile
+    pub fn settings(self) -> ProfileSettings {
+        let (high_cut_control, stereo_noise_canceling, soft_mute, sound_mode) = match self {
+            ListeningProfile::Default => (true, true, false, None),
+            ListeningProfile::WeakSignal => (true, true, true, None),
+            ListeningProfile::HiFi => (false, false, false, None),
+            ListeningProfile::Speech => (true, true, true, Some(SoundMode::Mono)),
+            ListeningProfile::Custom(settings) => return settings,
+        };
+        ProfileSettings {
+            high_cut_control,
+            stereo_noise_canceling,
+            soft_mute,
+            sound_mode,
+        }
+    }
+}
+
+impl<I2C, MODE> TEA5767<I2C, MODE> {
+    /// Get reception settings currently configured
+    pub fn profile_settings(&self) -> ProfileSettings {
+        ProfileSettings {
+            high_cut_control: self.high_cut_control,
+            stereo_noise_canceling: self.stereo_noise_canceling,
+            soft_mute: self.soft_mute,
+            sound_mode: Some(self.sound_mode),
+        }
+    }
+}
+
+impl<I2C, E, MODE> TEA5767<I2C, MODE>
+where
+    I2C: i2c::Write<Error = E> + i2c::Read<Error = E>
+{
+    /// Apply listening profile with a single register write, a profile without sound mode
+    /// restores the sound mode replaced by an earlier profile
+    pub fn set_listening_profile(&mut self, profile: ListeningProfile) -> Result<(), E> {
+        let settings = profile.settings();
+        self.high_cut_control = settings.high_cut_control;
+        self.stereo_noise_canceling = settings.stereo_noise_canceling;
+        self.soft_mute = settings.soft_mute;
+        match settings.sound_mode {
+            Some(sound_mode) => {
+                self.user_sound_mode.get_or_insert(self.sound_mode);
+                self.sound_mode = sound_mode;
+            }
+            None => {
+                if let Some(sound_mode) = self.user_sound_mode.take() {
+                    self.sound_mode = sound_mode;
+                }
+            }
+        }
+        self.upload()
+    }
+}
+
+/// Adaptive profile configuration
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AdaptiveConfig {
+    /// profile applied to weak reception
+    pub weak: ListeningProfile,
+    /// profile applied to strong reception
+    pub strong: ListeningProfile,
+    /// ADC output level below which reception turns weak, 0 - 15
+    pub weak_level: u8,
+    /// ADC output level from which reception turns strong, at least `weak_level`
+    pub strong_level: u8,
+}
+
+impl Default for AdaptiveConfig {
+    fn default() -> Self {
+        AdaptiveConfig {
+            weak: ListeningProfile::WeakSignal,
+            strong: ListeningProfile::HiFi,
+            weak_level: 6,
+            strong_level: 9,
+        }
+    }
+}
+
+/// Profile switching on signal level, levels between the thresholds keep the applied profile
+#[derive(Debug)]
+pub struct AdaptiveProfile {
+    config: AdaptiveConfig,
+    weak: Option<bool>,
+}
+
+impl AdaptiveProfile {
+    /// Create new adaptive profile, first check applies one of the profiles. A `strong_level`
+    /// below `weak_level` is raised to it.
+    pub fn new(config: AdaptiveConfig) -> Self {
+        AdaptiveProfile {
+            config: AdaptiveConfig {
+                strong_level: config.strong_level.max(config.weak_level),
+                ..config
+            },
+            weak: None,
+        }
+    }
+
+    /// Get adaptive profile configuration
+    pub fn config(&self) -> AdaptiveConfig {
+        self.config
+    }
+
+    /// Reception is weak, None before first check
+    pub fn is_weak(&self) -> Option<bool> {
+        self.weak
+    }
+
+    /// Measure signal level and apply the matching profile, returns profile when it has been
+    /// applied
+    pub fn check<I2C, E, MODE>(&mut self, tuner: &mut TEA5767<I2C, MODE>)
+        -> Result<Option<ListeningProfile>, E>
+    where
+        I2C: i2c::Write<Error = E> + i2c::Read<Error = E>,
+    {
+        let level = tuner.read_registers()?.level;
+        let weak = match self.weak {
+            Some(true) => level < self.config.strong_level,
+            Some(false) => level < self.config.weak_level,
+            None => level < self.config.weak_level,
+        };
+        if self.weak == Some(weak) {
+            return Ok(None);
+        }
+
+        self.weak = Some(weak);
+        let profile = match weak {
+            true => self.config.weak,
+            false => self.config.strong,
+        };
+        tuner.set_listening_profile(profile)?;
+        Ok(Some(profile))
+    }
+}
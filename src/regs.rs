@@ -1,21 +1,22 @@
 use embedded_hal::blocking::i2c::{Write, Read};
+use bit_field::BitField;
 use core::ops::Range;
 
 pub const DEVICE_ADDRESS: u8 =  0x60;
 
 // Hal I2C write binding
-pub fn write_data<I2C, E>(i2c: &mut I2C, data: [u8; 5]) -> Result<(), E>
+pub(crate) fn write_data<I2C, E>(i2c: &mut I2C, address: u8, data: [u8; 5]) -> Result<(), E>
 where I2C: Write<Error = E>,
 {
-    i2c.write(DEVICE_ADDRESS, &data)
+    i2c.write(address, &data)
 }
 
 // Hal I2C read binding
-pub fn read_data<I2C, E>(i2c: &mut I2C) -> Result<[u8; 5], E>
+pub(crate) fn read_data<I2C, E>(i2c: &mut I2C, address: u8) -> Result<[u8; 5], E>
 where I2C: Read<Error = E>,
 {
     let mut data: [u8; 5] = [0; 5];
-    i2c.read(DEVICE_ADDRESS, &mut data)?;
+    i2c.read(address, &mut data)?;
     Ok(data)
 }
 
@@ -60,16 +61,229 @@ pub const RM_DB2_PLL: Range<usize> = 0..8;
 
 //Read mode DataByte3
 pub const RM_DB3_STEREO: usize = 7;
-pub const WM_DB3_IF: Range<usize> = 0..6;
+pub const RM_DB3_IF: Range<usize> = 0..7;
 
 //Read mode DataByte4
 pub const RM_DB4_LEV: Range<usize> = 4..8;
 pub const RM_DB4_CI: Range<usize> = 1..4;
 
+/// Write mode register image, data bytes 1 - 5 sent to the device
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct WriteRegisters {
+    /// MUTE, both channels muted
+    pub mute: bool,
+    /// SM, search mode
+    pub search_mode: bool,
+    /// PLL, 14-bit synthesizer word
+    pub pll: u16,
+    /// SUD, search up
+    pub search_up: bool,
+    /// SSL, search stop level 1 - 3
+    pub search_stop_level: u8,
+    /// HLSI, high side LO injection
+    pub high_side_injection: bool,
+    /// MS, forced mono
+    pub mono: bool,
+    /// MR, right channel muted
+    pub mute_right: bool,
+    /// ML, left channel muted
+    pub mute_left: bool,
+    /// SWP1, software programmable port 1 high
+    pub port1: bool,
+    /// SWP2, software programmable port 2 high
+    pub port2: bool,
+    /// STBY, standby
+    pub standby: bool,
+    /// BL, Japanese band limits
+    pub japanese_band: bool,
+    /// XTAL, 32.768 kHz crystal
+    pub xtal: bool,
+    /// SMUTE, soft mute
+    pub soft_mute: bool,
+    /// HCC, high cut control
+    pub high_cut_control: bool,
+    /// SNC, stereo noise canceling
+    pub stereo_noise_canceling: bool,
+    /// SI, port 1 is search indicator output
+    pub search_indicator: bool,
+    /// PLLREF, 6.5 MHz reference
+    pub pllref: bool,
+    /// DTC, 75 μs de-emphasis
+    pub deemphasis_75us: bool,
+}
+
+/// Read mode register image, data bytes 1 - 5 received from the device
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ReadRegisters {
+    /// RF, station found or band limit reached
+    pub ready: bool,
+    /// BLF, band limit reached
+    pub band_limit: bool,
+    /// PLL, 14-bit synthesizer word after search or preset
+    pub pll: u16,
+    /// STEREO, stereo reception
+    pub stereo: bool,
+    /// IF, 7-bit IF counter result
+    pub if_counter: u8,
+    /// LEV, 4-bit ADC output level
+    pub level: u8,
+    /// CI, chip identification, 0 for TEA5767
+    pub chip_id: u8,
+}
+
+impl WriteRegisters {
+    /// Encode data bytes
+    pub fn to_bytes(&self) -> [u8; 5] {
+        let mut bytes = [0_u8; 5];
+        let pll = to_register_format_pll(self.pll as u32).unwrap();
+
+        bytes[0].set_bit(WM_DB1_MUTE, self.mute);
+        bytes[0].set_bit(WM_DB1_SM, self.search_mode);
+        bytes[0].set_bits(WM_DB1_PLL, pll[0]);
+        bytes[1].set_bits(WM_DB2_PLL, pll[1]);
+
+        bytes[2].set_bit(WM_DB3_SUD, self.search_up);
+        bytes[2].set_bits(WM_DB3_SSL, self.search_stop_level & 0b11);
+        bytes[2].set_bit(WM_DB3_HLSI, self.high_side_injection);
+        bytes[2].set_bit(WM_DB3_MS, self.mono);
+        bytes[2].set_bit(WM_DB3_MR, self.mute_right);
+        bytes[2].set_bit(WM_DB3_ML, self.mute_left);
+        bytes[2].set_bit(WM_DB3_SWP1, self.port1);
+
+        bytes[3].set_bit(WM_DB4_SWP2, self.port2);
+        bytes[3].set_bit(WM_DB4_STBY, self.standby);
+        bytes[3].set_bit(WM_DB4_BL, self.japanese_band);
+        bytes[3].set_bit(WM_DB4_XTAL, self.xtal);
+        bytes[3].set_bit(WM_DB4_SMUTE, self.soft_mute);
+        bytes[3].set_bit(WM_DB4_HCC, self.high_cut_control);
+        bytes[3].set_bit(WM_DB4_SNC, self.stereo_noise_canceling);
+        bytes[3].set_bit(WM_DB4_SI, self.search_indicator);
+
+        bytes[4].set_bit(WM_DB5_PLLREF, self.pllref);
+        bytes[4].set_bit(WM_DB5_DTC, self.deemphasis_75us);
+        bytes
+    }
+
+    /// Decode data bytes, e.g. captured I2C write
+    pub fn from_bytes(bytes: [u8; 5]) -> Self {
+        WriteRegisters {
+            mute: bytes[0].get_bit(WM_DB1_MUTE),
+            search_mode: bytes[0].get_bit(WM_DB1_SM),
+            pll: from_register_format_pll([bytes[0], bytes[1]]).unwrap() as u16,
+            search_up: bytes[2].get_bit(WM_DB3_SUD),
+            search_stop_level: bytes[2].get_bits(WM_DB3_SSL),
+            high_side_injection: bytes[2].get_bit(WM_DB3_HLSI),
+            mono: bytes[2].get_bit(WM_DB3_MS),
+            mute_right: bytes[2].get_bit(WM_DB3_MR),
+            mute_left: bytes[2].get_bit(WM_DB3_ML),
+            port1: bytes[2].get_bit(WM_DB3_SWP1),
+            port2: bytes[3].get_bit(WM_DB4_SWP2),
+            standby: bytes[3].get_bit(WM_DB4_STBY),
+            japanese_band: bytes[3].get_bit(WM_DB4_BL),
+            xtal: bytes[3].get_bit(WM_DB4_XTAL),
+            soft_mute: bytes[3].get_bit(WM_DB4_SMUTE),
+            high_cut_control: bytes[3].get_bit(WM_DB4_HCC),
+            stereo_noise_canceling: bytes[3].get_bit(WM_DB4_SNC),
+            search_indicator: bytes[3].get_bit(WM_DB4_SI),
+            pllref: bytes[4].get_bit(WM_DB5_PLLREF),
+            deemphasis_75us: bytes[4].get_bit(WM_DB5_DTC),
+        }
+    }
+}
+
+impl ReadRegisters {
+    /// Encode data bytes
+    pub fn to_bytes(&self) -> [u8; 5] {
+        let mut bytes = [0_u8; 5];
+        let pll = to_register_format_pll(self.pll as u32).unwrap();
+
+        bytes[0].set_bit(RM_DB1_RF, self.ready);
+        bytes[0].set_bit(RM_DB1_BLF, self.band_limit);
+        bytes[0].set_bits(RM_DB1_PLL, pll[0]);
+        bytes[1].set_bits(RM_DB2_PLL, pll[1]);
+
+        bytes[2].set_bit(RM_DB3_STEREO, self.stereo);
+        bytes[2].set_bits(RM_DB3_IF, self.if_counter & 0x7F);
+
+        bytes[3].set_bits(RM_DB4_LEV, self.level & 0x0F);
+        bytes[3].set_bits(RM_DB4_CI, self.chip_id & 0b111);
+        bytes
+    }
+
+    /// Decode data bytes, e.g. captured I2C read
+    pub fn from_bytes(bytes: [u8; 5]) -> Self {
+        ReadRegisters {
+            ready: bytes[0].get_bit(RM_DB1_RF),
+            band_limit: bytes[0].get_bit(RM_DB1_BLF),
+            pll: from_register_format_pll([bytes[0], bytes[1]]).unwrap() as u16,
+            stereo: bytes[2].get_bit(RM_DB3_STEREO),
+            if_counter: bytes[2].get_bits(RM_DB3_IF),
+            level: bytes[3].get_bits(RM_DB4_LEV),
+            chip_id: bytes[3].get_bits(RM_DB4_CI),
+        }
+    }
+}
+
+// change pll decimal format to register binary format
+pub(crate) fn to_register_format_pll(decimal: u32) -> Option<[u8; 2]> {
+    let pll_binary = [decimal.get_bits(8..14) as u8,
+        decimal.get_bits(0..8) as u8];
+    Some(pll_binary)
+}
+
+// change register binary format to decimal format
+pub(crate) fn from_register_format_pll(mut pll: [u8; 2]) -> Option<u32> {
+    pll[0].set_bits(6..8, 0b00);
+    let mut pll_decimal: u32 = 0;
+    // MSB
+    pll_decimal.set_bits(8..14, pll[0] as u32);
+    // LSB
+    pll_decimal.set_bits(0..8, pll[1] as u32);
+
+    Some(pll_decimal)
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::regs::*;
+
     #[test]
     fn test_write_data() {
 
     }
+
+    #[test]
+    fn test_write_registers() {
+        let registers = WriteRegisters {
+            pll: 11001,
+            search_up: true,
+            search_stop_level: 0b01,
+            high_side_injection: true,
+            mute_left: true,
+            xtal: true,
+            high_cut_control: true,
+            stereo_noise_canceling: true,
+            deemphasis_75us: true,
+            ..Default::default()
+        };
+        let bytes = [0b0010_1010, 0b1111_1001, 0b1011_0010, 0b0001_0110, 0b0100_0000];
+        assert_eq!(registers.to_bytes(), bytes);
+        assert_eq!(WriteRegisters::from_bytes(bytes), registers);
+    }
+
+    #[test]
+    fn test_read_registers() {
+        let bytes = [0b1010_1010, 0b1111_1001, 0b1011_0111, 0b1101_0000, 0];
+        let registers = ReadRegisters::from_bytes(bytes);
+        assert_eq!(registers, ReadRegisters {
+            ready: true,
+            band_limit: false,
+            pll: 11001,
+            stereo: true,
+            if_counter: 0x37,
+            level: 13,
+            chip_id: 0,
+        });
+        assert_eq!(registers.to_bytes(), bytes);
+    }
 }
\ No newline at end of file
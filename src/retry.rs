@@ -0,0 +1,177 @@
+//! Retry of transient I2C failures
+//!
+//! `RetryDevice` wraps the bus of the driver and repeats a failed transaction, so a single
+//! NACK on a long cable does not abort a whole search. Every transaction of the driver writes
+//! or reads all registers at once, which makes repeating it safe. embedded-hal 0.2 has no
+//! common I2C error kinds, errors to retry are chosen by a classifier function instead.
+
+use super::defs::*;
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::blocking::i2c;
+
+/// Retry policy
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RetryPolicy {
+    /// attempts of a transaction including the first one, at least 1
+    pub attempts: u8,
+    /// delay before the first retry, doubled before every next one, ms
+    pub backoff_ms: u16,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: 3,
+            backoff_ms: 1,
+        }
+    }
+}
+
+/// Retry counters
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RetryStats {
+    /// transactions repeated after a failure
+    pub retries: u32,
+    /// transactions which failed for good, error returned to the driver
+    pub failures: u32,
+}
+
+/// Delay provider of a device without backoff
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoDelay;
+
+impl DelayMs<u16> for NoDelay {
+    fn delay_ms(&mut self, _ms: u16) {}
+}
+
+/// I2C repeating failed transactions according to retry policy
+#[derive(Debug)]
+pub struct RetryDevice<I2C, E, D = NoDelay> {
+    i2c: I2C,
+    delay: D,
+    policy: RetryPolicy,
+    retryable: fn(&E) -> bool,
+    stats: RetryStats,
+}
+
+impl<I2C, E> RetryDevice<I2C, E> {
+    /// Create new device retrying every error, backoff needs a delay provider set by
+    /// `with_delay`
+    pub fn new(i2c: I2C, policy: RetryPolicy) -> Self {
+        RetryDevice {
+            i2c,
+            delay: NoDelay,
+            policy,
+            retryable: |_| true,
+            stats: RetryStats::default(),
+        }
+    }
+}
+
+impl<I2C, E, D> RetryDevice<I2C, E, D> {
+    /// Wait between attempts with delay provider
+    pub fn with_delay<T: DelayMs<u16>>(self, delay: T) -> RetryDevice<I2C, E, T> {
+        RetryDevice {
+            i2c: self.i2c,
+            delay,
+            policy: self.policy,
+            retryable: self.retryable,
+            stats: self.stats,
+        }
+    }
+
+    /// Retry only errors for which classifier returns true
+    pub fn with_classifier(mut self, retryable: fn(&E) -> bool) -> Self {
+        self.retryable = retryable;
+        self
+    }
+
+    /// Get retry policy
+    pub fn policy(&self) -> RetryPolicy {
+        self.policy
+    }
+
+    /// Get retry counters
+    pub fn stats(&self) -> RetryStats {
+        self.stats
+    }
+
+    /// Set retry counters to zero
+    pub fn reset_stats(&mut self) {
+        self.stats = RetryStats::default();
+    }
+
+    /// Remove device and give the bus back
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+}
+
+impl<I2C, E, D: DelayMs<u16>> RetryDevice<I2C, E, D> {
+    // Run transaction until it succeeds, fails with an error not to retry or runs out of attempts
+    fn run<F>(&mut self, mut transaction: F) -> Result<(), E>
+    where F: FnMut(&mut I2C) -> Result<(), E>
+    {
+        let mut backoff_ms = self.policy.backoff_ms;
+        let mut attempt = 1;
+        loop {
+            match transaction(&mut self.i2c) {
+                Ok(()) => return Ok(()),
+                Err(error) if attempt < self.policy.attempts && (self.retryable)(&error) => {
+                    self.stats.retries += 1;
+                    attempt += 1;
+                    if backoff_ms > 0 {
+                        self.delay.delay_ms(backoff_ms);
+                        backoff_ms = backoff_ms.saturating_mul(2);
+                    }
+                }
+                Err(error) => {
+                    self.stats.failures += 1;
+                    return Err(error);
+                }
+            }
+        }
+    }
+}
+
+impl<I2C, E, D> i2c::Write for RetryDevice<I2C, E, D>
+where
+    I2C: i2c::Write<Error = E>,
+    D: DelayMs<u16>,
+{
+    type Error = E;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), E> {
+        self.run(|i2c| i2c.write(address, bytes))
+    }
+}
+
+impl<I2C, E, D> i2c::Read for RetryDevice<I2C, E, D>
+where
+    I2C: i2c::Read<Error = E>,
+    D: DelayMs<u16>,
+{
+    type Error = E;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), E> {
+        self.run(|i2c| i2c.read(address, buffer))
+    }
+}
+
+impl<I2C, E, D, MODE> TEA5767<RetryDevice<I2C, E, D>, MODE> {
+    /// Get retry counters of the bus
+    pub fn retry_stats(&self) -> RetryStats {
+        self.i2c.stats()
+    }
+
+    /// Set retry counters of the bus to zero
+    pub fn reset_retry_stats(&mut self) {
+        self.i2c.reset_stats();
+    }
+}
@@ -0,0 +1,222 @@
+//! Simulated TEA5767 chip for host testing
+//!
+//! Answers the driver over the same I2C traits as the real device. Registers written by the
+//! driver are decoded, reads report the tuned station from a station list. Hardware search is
+//! completed immediately and reports ready, like the chip the ready flag is left clear by preset
+//! tuning. The PLL locks only when the clock setting matches the fitted clock, a station is
+//! received with its IF counter and level only then.
+
+use super::defs::CrystalFrequency;
+use super::regs::*;
+use embedded_hal::blocking::i2c::{Read, Write};
+#[allow(unused_imports)] // float methods are inherent with std
+use micromath::F32Ext;
+
+/// ADC level reported between stations
+pub const NOISE_LEVEL: u8 = 2;
+/// IF counter of a station received with exact 225 kHz IF, the reported value follows the
+/// oscillator frequency
+pub const TUNED_IF_COUNTER: u8 = 0x37;
+/// IF counter reported between stations
+pub const NOISE_IF_COUNTER: u8 = 0x12;
+
+/// Station received by simulated chip
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Station {
+    /// MHz
+    pub frequency: f32,
+    /// ADC level, 0 - 15
+    pub level: u8,
+    pub stereo: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum SimError {
+    /// Wrong address or transfer length
+    Nack,
+}
+
+/// Simulated TEA5767 chip with high side injection, 32.768 kHz crystal by default
+#[derive(Debug)]
+pub struct SimulatedTuner<'a> {
+    stations: &'a [Station],
+    registers: WriteRegisters,
+    pll: u16,
+    clock: CrystalFrequency,
+    crystal_ppm: f32,
+    ready: bool,
+    band_limit: bool,
+}
+
+impl<'a> SimulatedTuner<'a> {
+    /// Create chip receiving stations, tuned to 87.5 MHz
+    pub fn new(stations: &'a [Station]) -> Self {
+        SimulatedTuner {
+            stations,
+            registers: WriteRegisters {
+                pll: to_pll(87.5),
+                xtal: true,
+                ..WriteRegisters::default()
+            },
+            pll: to_pll(87.5),
+            clock: CrystalFrequency::Clk32_768Khz,
+            crystal_ppm: 0.0,
+            ready: false,
+            band_limit: false,
+        }
+    }
+
+    /// Replace received stations, e.g. to let a station fade out
+    pub fn set_stations(&mut self, stations: &'a [Station]) {
+        self.stations = stations;
+    }
+
+    /// Set reference clock fitted to the module, the PLL locks only with matching register setting
+    pub fn set_clock(&mut self, clock: CrystalFrequency) {
+        self.clock = clock;
+    }
+
+    /// Set crystal frequency offset, shifts the oscillator and so the reported IF counter, ppm
+    pub fn set_crystal_ppm(&mut self, ppm: f32) {
+        self.crystal_ppm = ppm;
+    }
+
+    /// Last written registers
+    pub fn registers(&self) -> [u8; 5] {
+        self.registers.to_bytes()
+    }
+
+    /// Last written register image
+    pub fn write_registers(&self) -> WriteRegisters {
+        self.registers
+    }
+
+    /// Tuned frequency, MHz
+    pub fn tuned_frequency(&self) -> f32 {
+        (self.oscillator() - 225_000.0) / 1_000_000.0
+    }
+
+    // PLL reference from fitted clock divided according to clock setting of the registers
+    fn reference(&self) -> f32 {
+        let clock = match self.clock {
+            CrystalFrequency::Clk32_768Khz => 32_768.0,
+            CrystalFrequency::Clk13Mhz => 13_000_000.0,
+            CrystalFrequency::Clk6_5MHz => 6_500_000.0,
+        };
+        let divider = match (self.registers.xtal, self.registers.pllref) {
+            (true, _) => 1.0,
+            (false, true) => 130.0,
+            (false, false) => 260.0,
+        };
+        clock * (1.0 + self.crystal_ppm / 1_000_000.0) / divider
+    }
+
+    // Oscillator frequency, Hz
+    fn oscillator(&self) -> f32 {
+        self.pll as f32 * self.reference() / 4.0
+    }
+
+    // Oscillator within VCO range
+    fn locked(&self) -> bool {
+        (70_000_000.0..=115_000_000.0).contains(&self.oscillator())
+    }
+
+    fn station(&self, frequency: f32) -> Option<Station> {
+        self.stations
+            .iter()
+            .copied()
+            .find(|s| (s.frequency - frequency).abs() < 0.05)
+    }
+
+    // IF counter on a station
+    fn if_counter(&self, frequency: f32) -> u8 {
+        ((self.oscillator() - frequency * 1_000_000.0) / 4096.0).round() as u8
+    }
+
+    // Hardware search from tuned frequency in 100 kHz steps
+    fn search(&mut self) {
+        let threshold = match self.registers.search_stop_level {
+            0b01 => 5,
+            0b10 => 7,
+            _ => 10,
+        };
+        let step = if self.registers.search_up { 0.1 } else { -0.1 };
+        let (low, high) = if self.registers.japanese_band {
+            (76.0, 91.0)
+        } else {
+            (87.5, 108.0)
+        };
+
+        let mut frequency = (self.tuned_frequency() * 10.0).round() / 10.0;
+        loop {
+            if frequency < low || frequency > high {
+                self.band_limit = true;
+                break;
+            }
+            if let Some(s) = self.station(frequency) {
+                if s.level >= threshold {
+                    self.pll = to_pll(frequency);
+                    break;
+                }
+            }
+            frequency = ((frequency + step) * 10.0).round() / 10.0;
+        }
+        self.ready = true;
+    }
+}
+
+impl<'a> Write for SimulatedTuner<'a> {
+    type Error = SimError;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), SimError> {
+        if address != DEVICE_ADDRESS || bytes.len() != 5 {
+            return Err(SimError::Nack);
+        }
+        self.registers = WriteRegisters::from_bytes([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4]]);
+        self.pll = self.registers.pll;
+        self.ready = false;
+        self.band_limit = false;
+        if self.locked() && self.registers.search_mode {
+            self.search();
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Read for SimulatedTuner<'a> {
+    type Error = SimError;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), SimError> {
+        if address != DEVICE_ADDRESS || buffer.len() != 5 {
+            return Err(SimError::Nack);
+        }
+        let station = self.station(self.tuned_frequency());
+        let mono = self.registers.mono;
+
+        if !self.locked() {
+            let registers = ReadRegisters { pll: self.pll, ..ReadRegisters::default() };
+            buffer.copy_from_slice(&registers.to_bytes());
+            return Ok(());
+        }
+
+        let registers = ReadRegisters {
+            ready: self.ready,
+            band_limit: self.band_limit,
+            pll: self.pll,
+            stereo: station.is_some_and(|s| s.stereo && !mono),
+            if_counter: station.map_or(NOISE_IF_COUNTER, |s| self.if_counter(s.frequency)),
+            level: station.map_or(NOISE_LEVEL, |s| s.level),
+            chip_id: 0,
+        };
+        buffer.copy_from_slice(&registers.to_bytes());
+        Ok(())
+    }
+}
+
+// PLL word for frequency, MHz
+fn to_pll(frequency: f32) -> u16 {
+    (4.0 * (frequency * 1_000_000.0 + 225_000.0) / 32_768.0) as u16
+}
@@ -0,0 +1,124 @@
+//! Relative stepping for buttons and rotary encoders
+//!
+//! `step` moves the tuned frequency by channel grid steps and wraps around at the ends of the
+//! frequency range. `StepControl` is called with a time stamp from any millisecond clock,
+//! multiplies steps following each other quickly and keeps audio muted during fast turns.
+
+use super::defs::*;
+use super::device::snap_to_grid;
+use embedded_hal::blocking::i2c;
+
+/// Step control configuration
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StepConfig {
+    /// steps closer to the previous one are a fast turn, ms
+    pub fast_interval_ms: u32,
+    /// step multiplier of a fast turn, 1 disables acceleration
+    pub acceleration: u8,
+    /// mute during a fast turn, audio is restored by `poll` once turning slows down
+    pub auto_mute: bool,
+}
+
+impl Default for StepConfig {
+    fn default() -> Self {
+        StepConfig {
+            fast_interval_ms: 100,
+            acceleration: 5,
+            auto_mute: true,
+        }
+    }
+}
+
+/// Stepping with acceleration and auto-mute
+#[derive(Debug)]
+pub struct StepControl {
+    config: StepConfig,
+    last_step: Option<u32>,
+    mute_state: Option<MuteChannel>,
+}
+
+impl StepControl {
+    /// Create new step control
+    pub fn new(config: StepConfig) -> Self {
+        StepControl {
+            config,
+            last_step: None,
+            mute_state: None,
+        }
+    }
+
+    /// Get step control configuration
+    pub fn config(&self) -> StepConfig {
+        self.config
+    }
+
+    /// Audio is muted by a fast turn
+    pub fn is_muted(&self) -> bool {
+        self.mute_state.is_some()
+    }
+
+    /// Step by steps, multiplied by acceleration when the previous step was a short time ago
+    pub fn step<I2C, E, MODE>(&mut self, tuner: &mut TEA5767<I2C, MODE>, steps: i16,
+                              now_ms: u32) -> Result<(), E>
+    where
+        I2C: i2c::Write<Error = E> + i2c::Read<Error = E>,
+        MODE: Tunable,
+    {
+        let interval_ms = self.config.fast_interval_ms;
+        let fast = self.last_step
+            .is_some_and(|last_step| now_ms.wrapping_sub(last_step) < interval_ms);
+        self.last_step = Some(now_ms);
+
+        let mut steps = steps as i32;
+        if fast {
+            steps *= self.config.acceleration.max(1) as i32;
+            if self.config.auto_mute && self.mute_state.is_none() {
+                self.mute_state = Some(tuner.mute);
+                tuner.mute = MuteChannel::Both;
+            }
+        }
+        tuner.step_by(steps)
+    }
+
+    /// Restore audio muted by a fast turn once no step came for the fast interval, returns
+    /// true when audio has been restored
+    pub fn poll<I2C, E, MODE>(&mut self, tuner: &mut TEA5767<I2C, MODE>, now_ms: u32)
+        -> Result<bool, E>
+    where
+        I2C: i2c::Write<Error = E> + i2c::Read<Error = E>,
+    {
+        let mute_state = match (self.mute_state, self.last_step) {
+            (Some(mute_state), Some(last_step))
+                if now_ms.wrapping_sub(last_step) >= self.config.fast_interval_ms => mute_state,
+            _ => return Ok(false),
+        };
+        self.mute_state = None;
+        tuner.mute = mute_state;
+        tuner.upload()?;
+        Ok(true)
+    }
+}
+
+impl<I2C, E, MODE: Tunable> TEA5767<I2C, MODE>
+where
+    I2C: i2c::Write<Error = E> + i2c::Read<Error = E>
+{
+    /// Move tuned frequency by steps of the channel grid, up for positive steps, wraps
+    /// around at the ends of the frequency range
+    pub fn step(&mut self, steps: i16) -> Result<(), E> {
+        self.step_by(steps as i32)
+    }
+
+    // Step on the grid of the channel plan, an off-grid frequency is snapped first
+    fn step_by(&mut self, steps: i32) -> Result<(), E> {
+        let plan = self.channel_plan();
+        let frequency = snap_to_grid(self.frequency, plan.frequency_range(),
+                                     plan.channel_spacing());
+        let channel = plan.grid_index(frequency).unwrap_or(0) as i32;
+        let channel = (channel + steps).rem_euclid(plan.channels() as i32);
+        self.set_frequency(plan.grid_frequency(channel as u16))
+    }
+}
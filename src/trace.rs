@@ -0,0 +1,354 @@
+//! I2C transaction tracing and replay
+//!
+//! `TraceDevice` wraps the bus of the driver and hands every transaction with a time stamp
+//! from a user clock to a trace sink, e.g. the `TraceBuffer` ring buffer. A recorded trace
+//! can be decoded into register images, or fed back to the driver through `ReplayDevice` to
+//! reproduce a field issue on the host.
+
+use super::defs::*;
+use super::regs::{ReadRegisters, WriteRegisters};
+use embedded_hal::blocking::i2c;
+
+/// Maximum number of bytes recorded per transaction
+pub const TRACE_BYTES: usize = 5;
+
+/// Transaction direction
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Direction {
+    Write,
+    Read,
+}
+
+/// Recorded I2C transaction
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TraceRecord {
+    /// time stamp from user clock
+    pub timestamp: u32,
+    pub direction: Direction,
+    pub address: u8,
+    /// transferred bytes, first `length` are valid
+    pub bytes: [u8; TRACE_BYTES],
+    pub length: u8,
+    /// transaction failed, read bytes are not valid
+    pub failed: bool,
+}
+
+/// Register image decoded from a recorded transaction
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum TraceRegisters {
+    Write(WriteRegisters),
+    Read(ReadRegisters),
+}
+
+impl TraceRecord {
+    const EMPTY: TraceRecord = TraceRecord {
+        timestamp: 0,
+        direction: Direction::Write,
+        address: 0,
+        bytes: [0; TRACE_BYTES],
+        length: 0,
+        failed: false,
+    };
+
+    fn new(timestamp: u32, direction: Direction, address: u8, data: &[u8], failed: bool) -> Self {
+        let length = data.len().min(TRACE_BYTES);
+        let mut bytes = [0; TRACE_BYTES];
+        bytes[..length].copy_from_slice(&data[..length]);
+        TraceRecord {
+            timestamp,
+            direction,
+            address,
+            bytes,
+            length: length as u8,
+            failed,
+        }
+    }
+
+    /// Get transferred bytes
+    pub fn data(&self) -> &[u8] {
+        &self.bytes[..self.length as usize]
+    }
+
+    /// Decode register image, None for failed or incomplete transaction
+    pub fn decode(&self) -> Option<TraceRegisters> {
+        if self.failed || self.length as usize != TRACE_BYTES {
+            return None;
+        }
+        match self.direction {
+            Direction::Write => Some(TraceRegisters::Write(WriteRegisters::from_bytes(self.bytes))),
+            Direction::Read => Some(TraceRegisters::Read(ReadRegisters::from_bytes(self.bytes))),
+        }
+    }
+}
+
+/// Receives recorded transactions
+pub trait TraceSink {
+    fn record(&mut self, record: &TraceRecord);
+}
+
+impl<F: FnMut(&TraceRecord)> TraceSink for F {
+    fn record(&mut self, record: &TraceRecord) {
+        self(record)
+    }
+}
+
+/// User clock time stamping recorded transactions
+pub trait TraceClock {
+    fn now(&mut self) -> u32;
+}
+
+impl<F: FnMut() -> u32> TraceClock for F {
+    fn now(&mut self) -> u32 {
+        self()
+    }
+}
+
+/// Ring buffer keeping the last N transactions
+#[derive(Debug)]
+pub struct TraceBuffer<const N: usize> {
+    records: [TraceRecord; N],
+    start: usize,
+    length: usize,
+    dropped: u32,
+}
+
+impl<const N: usize> TraceBuffer<N> {
+    /// Create empty buffer
+    pub fn new() -> Self {
+        TraceBuffer {
+            records: [TraceRecord::EMPTY; N],
+            start: 0,
+            length: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Number of kept transactions
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Number of transactions overwritten since creation or last clear
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+
+    /// Iterate kept transactions, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &TraceRecord> {
+        (0..self.length).map(move |i| &self.records[(self.start + i) % N])
+    }
+
+    /// Remove all transactions
+    pub fn clear(&mut self) {
+        self.start = 0;
+        self.length = 0;
+        self.dropped = 0;
+    }
+}
+
+impl<const N: usize> Default for TraceBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> TraceSink for TraceBuffer<N> {
+    fn record(&mut self, record: &TraceRecord) {
+        if N == 0 {
+            self.dropped += 1;
+        } else if self.length < N {
+            self.records[(self.start + self.length) % N] = *record;
+            self.length += 1;
+        } else {
+            self.records[self.start] = *record;
+            self.start = (self.start + 1) % N;
+            self.dropped += 1;
+        }
+    }
+}
+
+/// I2C recording every transaction to a trace sink
+#[derive(Debug)]
+pub struct TraceDevice<I2C, C, S> {
+    i2c: I2C,
+    clock: C,
+    sink: S,
+}
+
+impl<I2C, C: TraceClock, S: TraceSink> TraceDevice<I2C, C, S> {
+    /// Create new device
+    pub fn new(i2c: I2C, clock: C, sink: S) -> Self {
+        TraceDevice { i2c, clock, sink }
+    }
+
+    /// Get trace sink
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    /// Get trace sink, e.g. to clear it
+    pub fn sink_mut(&mut self) -> &mut S {
+        &mut self.sink
+    }
+
+    /// Remove device and give the bus and trace sink back
+    pub fn release(self) -> (I2C, S) {
+        (self.i2c, self.sink)
+    }
+}
+
+impl<I2C: i2c::Write, C: TraceClock, S: TraceSink> i2c::Write for TraceDevice<I2C, C, S> {
+    type Error = I2C::Error;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        let timestamp = self.clock.now();
+        let result = self.i2c.write(address, bytes);
+        let record = TraceRecord::new(timestamp, Direction::Write, address, bytes, result.is_err());
+        self.sink.record(&record);
+        result
+    }
+}
+
+impl<I2C: i2c::Read, C: TraceClock, S: TraceSink> i2c::Read for TraceDevice<I2C, C, S> {
+    type Error = I2C::Error;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        let timestamp = self.clock.now();
+        let result = self.i2c.read(address, buffer);
+        let record = TraceRecord::new(timestamp, Direction::Read, address, buffer, result.is_err());
+        self.sink.record(&record);
+        result
+    }
+}
+
+impl<I2C, C, S, MODE> TEA5767<TraceDevice<I2C, C, S>, MODE> {
+    /// Get trace sink of the bus
+    pub fn trace(&self) -> &S {
+        &self.i2c.sink
+    }
+
+    /// Get trace sink of the bus, e.g. to clear it
+    pub fn trace_mut(&mut self) -> &mut S {
+        &mut self.i2c.sink
+    }
+}
+
+/// Replay errors, with index of the transaction in the trace
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum ReplayError {
+    /// Driver transaction differs from the recorded one
+    Mismatch(usize),
+    /// Recorded transaction failed
+    Failed(usize),
+    /// Trace has no more transactions
+    End,
+}
+
+/// I2C answering the driver from a recorded trace. Writes have to match the recorded ones,
+/// reads return the recorded bytes
+#[derive(Debug)]
+pub struct ReplayDevice<I> {
+    records: I,
+    position: usize,
+}
+
+impl<'a, I: Iterator<Item = &'a TraceRecord>> ReplayDevice<I> {
+    /// Create new device replaying records
+    pub fn new<T: IntoIterator<IntoIter = I>>(records: T) -> Self {
+        ReplayDevice {
+            records: records.into_iter(),
+            position: 0,
+        }
+    }
+
+    /// Number of replayed transactions
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    // Next record, it has to match direction, address and length of driver transaction
+    fn next(&mut self, direction: Direction, address: u8, length: usize)
+        -> Result<&'a TraceRecord, ReplayError> {
+        let index = self.position;
+        let record = self.records.next().ok_or(ReplayError::End)?;
+        self.position += 1;
+
+        if record.direction != direction || record.address != address
+            || record.length as usize != length.min(TRACE_BYTES) {
+            return Err(ReplayError::Mismatch(index));
+        }
+        if record.failed {
+            return Err(ReplayError::Failed(index));
+        }
+        Ok(record)
+    }
+}
+
+impl<'a, I: Iterator<Item = &'a TraceRecord>> i2c::Write for ReplayDevice<I> {
+    type Error = ReplayError;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), ReplayError> {
+        let index = self.position;
+        let record = self.next(Direction::Write, address, bytes.len())?;
+        match record.data() == &bytes[..record.length as usize] {
+            true => Ok(()),
+            false => Err(ReplayError::Mismatch(index)),
+        }
+    }
+}
+
+impl<'a, I: Iterator<Item = &'a TraceRecord>> i2c::Read for ReplayDevice<I> {
+    type Error = ReplayError;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), ReplayError> {
+        let record = self.next(Direction::Read, address, buffer.len())?;
+        buffer[..record.length as usize].copy_from_slice(record.data());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::trace::*;
+
+    fn record(timestamp: u32) -> TraceRecord {
+        TraceRecord::new(timestamp, Direction::Read, 0x60, &[1, 2, 3, 4, 5], false)
+    }
+
+    #[test]
+    fn test_trace_buffer_wraps() {
+        let mut buffer = TraceBuffer::<3>::new();
+        for timestamp in 0..5 {
+            buffer.record(&record(timestamp));
+        }
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(buffer.dropped(), 2);
+        let mut timestamps = buffer.iter().map(|record| record.timestamp);
+        assert_eq!(timestamps.next(), Some(2));
+        assert_eq!(timestamps.next(), Some(3));
+        assert_eq!(timestamps.next(), Some(4));
+        assert_eq!(timestamps.next(), None);
+    }
+
+    #[test]
+    fn test_trace_record_decode() {
+        let mut record = record(0);
+        assert!(matches!(record.decode(), Some(TraceRegisters::Read(_))));
+        record.failed = true;
+        assert_eq!(record.decode(), None);
+    }
+}
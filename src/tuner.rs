@@ -0,0 +1,70 @@
+use super::defs::*;
+use embedded_hal::blocking::i2c;
+
+/// Chip agnostic FM tuner, lets applications be written generically over tuner drivers
+pub trait FmTuner {
+    type Error;
+
+    /// Tune to specific radio frequency, MHz
+    fn tune(&mut self, frequency: f32) -> Result<(), Self::Error>;
+
+    /// Search for next radio station from current frequency
+    fn seek(&mut self, direction: SearchModeDirection) -> Result<SearchStatus, Self::Error>;
+
+    /// Read frequency, reception and signal level
+    fn status(&mut self) -> Result<TunerStatus, Self::Error>;
+
+    /// Mute or unmute both channels
+    fn set_mute(&mut self, mute: bool) -> Result<(), Self::Error>;
+
+    /// Set mono or stereo sound mode
+    fn set_sound_mode(&mut self, sound_mode: SoundMode) -> Result<(), Self::Error>;
+
+    /// Enable or disable standby mode
+    fn set_standby(&mut self, standby: bool) -> Result<(), Self::Error>;
+}
+
+/// Implemented in every mode with tuning methods. `set_standby` switches the standby bit at
+/// runtime also in the `Active` typestate, use `into_standby` to keep the typestate accurate
+impl<I2C, E, MODE: Tunable> FmTuner for TEA5767<I2C, MODE>
+where
+    I2C: i2c::Write<Error = E> + i2c::Read<Error = E>
+{
+    type Error = E;
+
+    fn tune(&mut self, frequency: f32) -> Result<(), E> {
+        self.set_frequency(frequency)
+    }
+
+    fn seek(&mut self, direction: SearchModeDirection) -> Result<SearchStatus, E> {
+        let signal_level = self.search_adc_level;
+        let from_frequency = self.frequency;
+        match direction {
+            SearchModeDirection::Up => self.search_up(signal_level, from_frequency),
+            SearchModeDirection::Down => self.search_down(signal_level, from_frequency),
+        }
+    }
+
+    fn status(&mut self) -> Result<TunerStatus, E> {
+        self.get_status()
+    }
+
+    fn set_mute(&mut self, mute: bool) -> Result<(), E> {
+        match mute {
+            true => self.mute(),
+            false => self.unmute(),
+        }
+    }
+
+    fn set_sound_mode(&mut self, sound_mode: SoundMode) -> Result<(), E> {
+        match sound_mode {
+            SoundMode::Stereo => self.set_stereo(),
+            SoundMode::Mono => self.set_mono(),
+        }
+    }
+
+    fn set_standby(&mut self, standby: bool) -> Result<(), E> {
+        self.standby = standby;
+        self.upload()
+    }
+}
@@ -0,0 +1,55 @@
+#![cfg(feature = "linux")]
+
+use std::process::Command;
+
+fn cli(args: &[&str]) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_tea5767-cli"))
+        .arg("--sim")
+        .args(args)
+        .output()
+        .unwrap();
+    (output.status.success(), String::from_utf8(output.stdout).unwrap())
+}
+
+#[test]
+fn test_cli_scan() {
+    assert_eq!(cli(&["--json", "scan"]), (true, String::from("{\"stations\":[89.9,95.1,101.3,104.7]}\n")));
+    assert_eq!(cli(&["scan", "high"]), (true, String::from("101.3 MHz\n104.7 MHz\n")));
+}
+
+#[test]
+fn test_cli_seek_and_tune() {
+    assert_eq!(cli(&["seek", "up", "high"]), (true, String::from("found 101.3 MHz\n")));
+    assert_eq!(cli(&["--json", "tune", "95.1"]), (true, String::from("{\"frequency\":95.1}\n")));
+}
+
+#[test]
+fn test_cli_region_and_clock() {
+    // Japanese range ends at 90.0 MHz
+    assert_eq!(cli(&["--region", "japan", "tune", "95.1"]), (true, String::from("tuned 90.0 MHz\n")));
+    assert_eq!(cli(&["--clock", "13m", "tune", "95.1"]), (true, String::from("tuned 95.1 MHz\n")));
+    assert!(!cli(&["--region", "mars", "status"]).0);
+    assert!(!cli(&["--clock", "1m", "status"]).0);
+}
+
+#[test]
+fn test_cli_status_watch() {
+    let (success, output) = cli(&["--json", "status", "--watch", "--interval", "1", "--count", "3"]);
+    assert!(success);
+    assert_eq!(output.lines().count(), 3);
+    assert!(output.starts_with("{\"frequency\":87.5,\"stereo\":false,\"level\":2,"));
+}
+
+#[test]
+fn test_cli_dump_registers() {
+    let (success, output) = cli(&["--json", "dump-registers"]);
+    assert!(success);
+    assert!(output.contains("\"if_counter\":18,\"level\":2,\"chip_id\":0"));
+}
+
+#[test]
+fn test_cli_errors() {
+    assert!(!cli(&["tune"]).0);
+    assert!(!cli(&["rewind"]).0);
+    assert!(!cli(&[]).0);
+}
@@ -1,3 +1,144 @@
-pub fn setup() {
-    // the setup code
-}
\ No newline at end of file
+// Simulated TEA5767 chip recording every register write
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::blocking::i2c::{Read, Write};
+use tea5767::sim;
+
+pub use tea5767::sim::{SimError, Station, NOISE_IF_COUNTER, NOISE_LEVEL, TUNED_IF_COUNTER};
+
+#[derive(Debug)]
+pub struct SimulatedTuner {
+    pub writes: Vec<[u8; 5]>,
+    pub reads: usize,
+    // next transactions NACKed
+    pub fail_next: usize,
+    // transactions passed before fail_next applies
+    pub pass_next: usize,
+    chip: sim::SimulatedTuner<'static>,
+}
+
+impl SimulatedTuner {
+    pub fn new(stations: &'static [Station]) -> Self {
+        SimulatedTuner {
+            writes: Vec::new(),
+            reads: 0,
+            fail_next: 0,
+            pass_next: 0,
+            chip: sim::SimulatedTuner::new(stations),
+        }
+    }
+
+    fn fail(&mut self) -> Result<(), SimError> {
+        if self.pass_next > 0 {
+            self.pass_next -= 1;
+            return Ok(());
+        }
+        if self.fail_next > 0 {
+            self.fail_next -= 1;
+            return Err(SimError::Nack);
+        }
+        Ok(())
+    }
+
+    pub fn last_write(&self) -> [u8; 5] {
+        *self.writes.last().expect("no write")
+    }
+
+    pub fn tuned_frequency(&self) -> f32 {
+        self.chip.tuned_frequency()
+    }
+
+    pub fn set_stations(&mut self, stations: &'static [Station]) {
+        self.chip.set_stations(stations);
+    }
+
+    pub fn set_crystal_ppm(&mut self, ppm: f32) {
+        self.chip.set_crystal_ppm(ppm);
+    }
+
+    pub fn set_clock(&mut self, clock: tea5767::defs::CrystalFrequency) {
+        self.chip.set_clock(clock);
+    }
+}
+
+impl Write for SimulatedTuner {
+    type Error = SimError;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), SimError> {
+        self.fail()?;
+        self.chip.write(address, bytes)?;
+        self.writes.push(self.chip.registers());
+        Ok(())
+    }
+}
+
+impl Read for SimulatedTuner {
+    type Error = SimError;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), SimError> {
+        self.fail()?;
+        self.reads += 1;
+        self.chip.read(address, buffer)
+    }
+}
+
+pub fn frequency_to_pll(frequency: f32) -> u16 {
+    (4.0 * (frequency * 1_000_000.0 + 225_000.0) / 32_768.0) as u16
+}
+
+// Delay provider which only accumulates requested time
+#[derive(Debug, Default)]
+pub struct NoDelay {
+    pub elapsed_ms: u32,
+}
+
+impl DelayMs<u16> for NoDelay {
+    fn delay_ms(&mut self, ms: u16) {
+        self.elapsed_ms += ms as u32;
+    }
+}
+
+pub fn setup(stations: &'static [Station]) -> SimulatedTuner {
+    SimulatedTuner::new(stations)
+}
+
+// Simulated TCA9548A multiplexer with a tuner behind each channel
+#[derive(Debug, Default)]
+pub struct SimulatedMux {
+    pub tuners: Vec<SimulatedTuner>,
+    pub selects: usize,
+    selected: Option<usize>,
+}
+
+impl SimulatedMux {
+    pub fn new(tuners: Vec<SimulatedTuner>) -> Self {
+        SimulatedMux { tuners, ..Default::default() }
+    }
+
+    fn tuner(&mut self) -> Result<&mut SimulatedTuner, SimError> {
+        match self.selected {
+            Some(channel) => self.tuners.get_mut(channel).ok_or(SimError::Nack),
+            None => Err(SimError::Nack),
+        }
+    }
+}
+
+impl Write for SimulatedMux {
+    type Error = SimError;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), SimError> {
+        if address == 0x70 {
+            self.selects += 1;
+            self.selected = Some(bytes[0].trailing_zeros() as usize);
+            return Ok(());
+        }
+        self.tuner()?.write(address, bytes)
+    }
+}
+
+impl Read for SimulatedMux {
+    type Error = SimError;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), SimError> {
+        self.tuner()?.read(address, buffer)
+    }
+}
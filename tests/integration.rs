@@ -4,7 +4,998 @@
 
 mod common;
 
+use common::*;
+use tea5767::defs::*;
+
+const STATIONS: [Station; 3] = [
+    Station { frequency: 89.9, level: 6, stereo: false },
+    Station { frequency: 95.1, level: 9, stereo: true },
+    Station { frequency: 101.3, level: 13, stereo: true },
+];
+
 #[test]
 fn test_tea67_new() {
+    let radio_tuner = TEA5767::new(setup(&STATIONS), 95.1, BandLimits::EuropeUS,
+                                   SoundMode::Stereo).unwrap();
+    let sim = radio_tuner.destroy().unwrap();
+    assert_eq!(sim.writes.len(), 1);
+    assert_eq!(frequency_to_pll(95.1), ((sim.last_write()[0] as u16 & 0x3F) << 8)
+        | sim.last_write()[1] as u16);
+}
+
+#[test]
+fn test_search_stops_beyond_range() {
+    use tea5767::events::TunerEvent;
+
+    // between the 90 MHz end of the Japan range and the 91 MHz band limit
+    const BEYOND: [Station; 2] = [
+        Station { frequency: 80.0, level: 13, stereo: true },
+        Station { frequency: 90.5, level: 13, stereo: true },
+    ];
+    let mut radio_tuner = TEA5767::new(setup(&BEYOND), 88.0, BandLimits::Japanese,
+                                       SoundMode::Stereo).unwrap();
+    radio_tuner.set_region(Region::Japan).unwrap();
+    let mut events = Vec::new();
+    radio_tuner.service(&mut |event| events.push(event)).unwrap();
+
+    assert_eq!(radio_tuner.search_up(SearchAdcLevel::High, 88.0).unwrap(),
+               SearchStatus::Failure);
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 90.0);
+    events.clear();
+    radio_tuner.service(&mut |event| events.push(event)).unwrap();
+    assert!(events.contains(&TunerEvent::BandLimitReached));
+
+    // stop within the range
+    assert_eq!(radio_tuner.search_down(SearchAdcLevel::High, 88.0).unwrap(),
+               SearchStatus::Success);
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 80.0);
+}
+
+#[test]
+fn test_search_bus_error_restores_mute() {
+    use std::cell::RefCell;
+    use tea5767::bus::RefCellDevice;
+    use tea5767::regs::WriteRegisters;
+
+    let criteria = SearchCriteria::default();
+    for search in 0..4 {
+        let bus = RefCell::new(setup(&STATIONS));
+        let mut radio_tuner = TEA5767::new(RefCellDevice::new(&bus), 92.0, BandLimits::EuropeUS,
+                                           SoundMode::Stereo).unwrap();
+        let mut delay = NoDelay::default();
+
+        // start frequency and mute written, first read fails
+        bus.borrow_mut().pass_next = 2;
+        bus.borrow_mut().fail_next = 1;
+        let result = match search {
+            0 => radio_tuner.search_up(SearchAdcLevel::High, 92.0),
+            1 => radio_tuner.search_down(SearchAdcLevel::High, 92.0),
+            2 => radio_tuner.search_software(SearchModeDirection::Up, criteria, 92.0, &mut delay),
+            _ => radio_tuner.search_strongest(criteria, &mut delay),
+        };
+        assert!(result.is_err());
+
+        assert_eq!(radio_tuner.config().mute, MuteChannel::None);
+        let registers = WriteRegisters::from_bytes(bus.borrow().last_write());
+        assert!(!registers.mute && !registers.search_mode);
+        assert_eq!(radio_tuner.get_frequency().unwrap(), 92.0);
+    }
+}
+
+#[test]
+fn test_measure_channel_bus_error() {
+    use std::cell::RefCell;
+    use tea5767::bus::RefCellDevice;
+    use tea5767::regs::WriteRegisters;
+
+    let bus = RefCell::new(setup(&STATIONS));
+    let mut radio_tuner = TEA5767::new(RefCellDevice::new(&bus), 95.1, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    let mut delay = NoDelay::default();
+
+    // retune fails, then the read of the alternative
+    for &pass in [0, 1].iter() {
+        bus.borrow_mut().pass_next = pass;
+        bus.borrow_mut().fail_next = 1;
+        assert!(radio_tuner.measure_channel(101.3, 10, &mut delay).is_err());
+
+        assert_eq!(radio_tuner.config().mute, MuteChannel::None);
+        let registers = WriteRegisters::from_bytes(bus.borrow().last_write());
+        assert!(!registers.mute);
+        assert_eq!(radio_tuner.get_frequency().unwrap(), 95.1);
+    }
+
+    // later writes keep tuned frequency and audio
+    radio_tuner.set_soft_mute().unwrap();
+    let registers = WriteRegisters::from_bytes(bus.borrow().last_write());
+    assert!(!registers.mute);
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 95.1);
+}
+
+#[test]
+fn test_search_software_level() {
+    let mut radio_tuner = TEA5767::new(setup(&STATIONS), 88.0, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    let mut delay = NoDelay::default();
+    let criteria = SearchCriteria { adc_level: 8, ..Default::default() };
+
+    let status = radio_tuner.search_software(SearchModeDirection::Up, criteria, 88.0,
+                                             &mut delay).unwrap();
+    assert_eq!(status, SearchStatus::Success);
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 95.1);
+    assert_eq!(delay.elapsed_ms, 71 * criteria.dwell_ms as u32);
+}
+
+#[test]
+fn test_search_software_stereo() {
+    let mut radio_tuner = TEA5767::new(setup(&STATIONS), 105.0, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    let mut delay = NoDelay::default();
+    let criteria = SearchCriteria { adc_level: 0, stereo: true, ..Default::default() };
+
+    let status = radio_tuner.search_software(SearchModeDirection::Down, criteria, 100.0,
+                                             &mut delay).unwrap();
+    assert_eq!(status, SearchStatus::Success);
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 95.1);
+}
+
+#[test]
+fn test_search_software_band_limit() {
+    let mut radio_tuner = TEA5767::new(setup(&STATIONS), 102.0, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    let mut delay = NoDelay::default();
+    let criteria = SearchCriteria { adc_level: 14, ..Default::default() };
+
+    let status = radio_tuner.search_software(SearchModeDirection::Up, criteria, 102.0,
+                                             &mut delay).unwrap();
+    assert_eq!(status, SearchStatus::Failure);
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 102.0);
+}
+
+#[test]
+fn test_search_software_level_clamped() {
+    // full scale station meets any level above 15
+    const FULL_SCALE: [Station; 1] = [Station { frequency: 101.3, level: 15, stereo: true }];
+
+    let mut radio_tuner = TEA5767::new(setup(&FULL_SCALE), 100.0, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    let mut delay = NoDelay::default();
+    let criteria = SearchCriteria { adc_level: 20, ..Default::default() };
+
+    let status = radio_tuner.search_software(SearchModeDirection::Up, criteria, 100.0,
+                                             &mut delay).unwrap();
+    assert_eq!(status, SearchStatus::Success);
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 101.3);
+}
+
+#[test]
+fn test_set_region() {
+    let mut radio_tuner = TEA5767::new(setup(&STATIONS), 100.0, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    radio_tuner.set_region(Region::USA).unwrap();
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 99.9);
+
+    radio_tuner.set_region(Region::Japan).unwrap();
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 90.0);
+
+    let sim = radio_tuner.destroy().unwrap();
+    // Japanese band limits, 50 μs de-emphasis
+    assert_eq!(sim.last_write()[3] & 0b0010_0000, 0b0010_0000);
+    assert_eq!(sim.last_write()[4] & 0b0100_0000, 0);
+}
+
+#[test]
+fn test_set_config_invalid() {
+    let mut radio_tuner = TEA5767::new(setup(&STATIONS), 100.0, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    let config = radio_tuner.config();
+
+    // search step rounding to no movement, range beyond band limits, empty range
+    let seek_step = TunerConfig { seek_step: 0.001, ..config };
+    let range = TunerConfig { frequency_range: (76.0, 95.0), ..config };
+    let empty = TunerConfig { frequency_range: (100.0, 100.0), ..config };
+    for invalid in [seek_step, range, empty].iter() {
+        assert!(!invalid.is_valid());
+        assert_eq!(radio_tuner.set_config(invalid), Err(ConfigError::Invalid));
+    }
+    assert_eq!(radio_tuner.config(), config);
+
+    let sim = radio_tuner.destroy().unwrap();
+    assert_eq!(sim.writes.len(), 1);
+}
+
+#[test]
+fn test_typestate_standby() {
+    let radio_tuner = TEA5767::new(setup(&STATIONS), 95.1, BandLimits::EuropeUS,
+                                   SoundMode::Stereo).unwrap();
+    let mut radio_tuner: TEA5767<_, Active> = radio_tuner.into_active().unwrap();
+    radio_tuner.set_frequency(101.3).unwrap();
+
+    let mut radio_tuner: TEA5767<_, Standby> = radio_tuner.into_standby().unwrap();
+    radio_tuner.mute().unwrap();
+    let mut radio_tuner = radio_tuner.wake().unwrap();
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 101.3);
+
+    let sim = radio_tuner.destroy().unwrap();
+    let writes = &sim.writes;
+    // standby bit set only while in Standby typestate
+    assert_eq!(writes[writes.len() - 3][3] & 0b0100_0000, 0b0100_0000);
+    assert_eq!(writes[writes.len() - 2][3] & 0b0100_0000, 0b0100_0000);
+    assert_eq!(writes[writes.len() - 1][3] & 0b0100_0000, 0);
+}
+
+#[test]
+fn test_search_restores_mute() {
+    let mut radio_tuner = TEA5767::new(setup(&STATIONS), 88.0, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    radio_tuner.mute_left().unwrap();
+
+    let status = radio_tuner.search_up(SearchAdcLevel::Mid, 88.0).unwrap();
+    assert_eq!(status, SearchStatus::Success);
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 95.1);
+
+    let sim = radio_tuner.destroy().unwrap();
+    // muted while searching, left channel muted again afterwards
+    assert!(sim.writes.iter().any(|w| w[0] & 0b1100_0000 == 0b1100_0000));
+    assert_eq!(sim.last_write()[0] & 0b1000_0000, 0);
+    assert_eq!(sim.last_write()[2] & 0b0000_0110, sim.writes[1][2] & 0b0000_0110);
+    assert_ne!(sim.writes[1][2] & 0b0000_0110, 0);
+    // ML bit
+    assert_eq!(sim.writes[1][2] & 0b0000_0110, 0b0000_0010);
+}
+
+#[test]
+fn test_search_soft_mute_policy() {
+    let mut radio_tuner = TEA5767::new(setup(&STATIONS), 100.0, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    radio_tuner.set_search_mute_policy(SearchMutePolicy::SoftMute);
+    let mut delay = NoDelay::default();
+
+    let status = radio_tuner.search_software(SearchModeDirection::Up,
+                                             SearchCriteria::default(), 100.0,
+                                             &mut delay).unwrap();
+    assert_eq!(status, SearchStatus::Success);
+
+    let sim = radio_tuner.destroy().unwrap();
+    // soft mute during search, never hard muted
+    assert!(sim.writes.iter().all(|w| w[0] & 0b1000_0000 == 0));
+    assert_eq!(sim.writes[2][3] & 0b0000_1000, 0b0000_1000);
+    assert_eq!(sim.last_write()[3] & 0b0000_1000, 0);
+}
+
+fn seek_and_report<T: tea5767::tuner::FmTuner>(tuner: &mut T) -> Result<TunerStatus, T::Error> {
+    tuner.set_mute(true)?;
+    tuner.seek(SearchModeDirection::Up)?;
+    tuner.set_mute(false)?;
+    tuner.status()
+}
+
+#[test]
+fn test_fm_tuner_trait() {
+    let mut radio_tuner = TEA5767::new(setup(&STATIONS), 96.0, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    let status = seek_and_report(&mut radio_tuner).unwrap();
+    assert_eq!(status.frequency, 101.3);
+    assert_eq!(status.sound_mode, SoundMode::Stereo);
+    assert_eq!(status.signal_level, 13);
+
+    // typestate API
+    let mut radio_tuner = radio_tuner.into_active().unwrap();
+    radio_tuner.set_frequency(96.0).unwrap();
+    let status = seek_and_report(&mut radio_tuner).unwrap();
+    assert_eq!(status.frequency, 101.3);
+}
+
+#[cfg(feature = "presets")]
+#[test]
+fn test_restore_presets() {
+    use tea5767::presets::Presets;
+
+    let mut radio_tuner = TEA5767::new(setup(&STATIONS), 95.1, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    radio_tuner.set_region(Region::Japan).unwrap();
+    radio_tuner.set_frequency(89.9).unwrap();
+    radio_tuner.set_crystal_ppm(-40.0).unwrap();
+    let mut presets = Presets::new(radio_tuner.config(), radio_tuner.get_frequency().unwrap());
+    presets.crystal_ppm = radio_tuner.crystal_ppm();
+    let sim = radio_tuner.destroy().unwrap();
+    let saved = sim.last_write();
+
+    let mut radio_tuner = TEA5767::new(setup(&STATIONS), 101.3, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    radio_tuner.restore(&Presets::from_bytes(&presets.to_bytes()).unwrap()).unwrap();
+    assert_eq!(radio_tuner.config(), presets.config);
+    assert_eq!(radio_tuner.crystal_ppm(), -40.0);
+    assert_eq!(radio_tuner.destroy().unwrap().last_write(), saved);
+}
+
+#[test]
+fn test_mux_two_tuners() {
+    use tea5767::mux::{Tca9548a, TCA9548A_ADDRESS};
+
+    let bus = SimulatedMux::new(vec![setup(&STATIONS), setup(&STATIONS)]);
+    let mux = Tca9548a::new(bus, TCA9548A_ADDRESS);
+
+    let mut listening = TEA5767::new(mux.channel(0).unwrap(), 95.1, BandLimits::EuropeUS,
+                                     SoundMode::Stereo).unwrap();
+    let mut scanning = TEA5767::new(mux.channel(1).unwrap(), 88.0, BandLimits::EuropeUS,
+                                    SoundMode::Stereo).unwrap();
+    scanning.search_up(SearchAdcLevel::High, 88.0).unwrap();
+    assert_eq!(scanning.get_frequency().unwrap(), 101.3);
+    assert_eq!(listening.get_frequency().unwrap(), 95.1);
+
+    listening.destroy().unwrap();
+    scanning.destroy().unwrap();
+    let bus = mux.destroy();
+    assert_eq!(bus.tuners[0].tuned_frequency().round(), 95.0);
+    assert_eq!(bus.tuners[1].writes.len(), 5);
+    // channel 1 kept selected for the whole search
+    assert_eq!(bus.selects, 3);
+}
+
+#[test]
+fn test_mux_channel_range() {
+    use tea5767::mux::{Tca9548a, TCA9548A_ADDRESS};
+
+    let mux = Tca9548a::new(SimulatedMux::new(vec![setup(&STATIONS)]), TCA9548A_ADDRESS);
+    assert!(mux.channel(7).is_some());
+    // channel 9 would be channel 1 in the control register
+    assert!(mux.channel(8).is_none());
+    assert!(mux.channel(9).is_none());
+}
+
+#[test]
+fn test_shared_bus() {
+    use std::cell::RefCell;
+    use tea5767::bus::RefCellDevice;
+
+    let bus = RefCell::new(setup(&STATIONS));
+    let mut radio_tuner = TEA5767::new(RefCellDevice::new(&bus), 95.1, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    // bus is still usable by other devices
+    bus.borrow_mut().reads = 0;
+    assert_eq!(radio_tuner.get_signal_level().unwrap(), 9);
+    assert_eq!(bus.borrow().reads, 1);
+}
+
+#[test]
+fn test_detached_bus() {
+    let radio_tuner = TEA5767::new(setup(&STATIONS), 95.1, BandLimits::EuropeUS,
+                                   SoundMode::Stereo).unwrap();
+    let (mut sim, mut radio_tuner) = radio_tuner.detach();
+
+    radio_tuner.with_bus(&mut sim, |tuner| tuner.set_frequency(101.3)).unwrap();
+    // bus is free between calls
+    assert_eq!(sim.writes.len(), 2);
+    let level = radio_tuner.with_bus(&mut sim, |tuner| tuner.get_signal_level()).unwrap();
+    assert_eq!(level, 13);
+
+    let mut radio_tuner = radio_tuner.attach_bus(sim);
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 101.3);
+}
+
+#[test]
+fn test_attach_running_tuner() {
+    let radio_tuner = TEA5767::new(setup(&STATIONS), 101.3, BandLimits::EuropeUS,
+                                   SoundMode::Stereo).unwrap();
+    let mut sim = radio_tuner.destroy().unwrap();
+    let running = sim.last_write();
+    sim.writes.clear();
+
+    let mut radio_tuner = TEA5767::attach(sim, TunerConfig::default()).unwrap();
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 101.3);
+    assert_eq!(radio_tuner.get_sound_mode().unwrap(), SoundMode::Stereo);
+
+    radio_tuner.set_high_cut_control().unwrap();
+    let sim = radio_tuner.destroy().unwrap();
+    // nothing written on attach, state rebuilt from the device
+    assert_eq!(sim.writes.len(), 1);
+    assert_eq!(sim.last_write(), running);
+}
+
+#[test]
+fn test_command_protocol() {
+    use tea5767::command::{process, Reply};
+
+    let mut radio_tuner = TEA5767::new(setup(&STATIONS), 88.0, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    assert_eq!(process("SEEK UP HIGH", &mut radio_tuner),
+               Reply::Seek(SearchStatus::Success, 101.3));
+    assert_eq!(process("TUNE 95.1", &mut radio_tuner), Reply::Tuned(95.1));
+    match process("STATUS", &mut radio_tuner) {
+        Reply::Status(status) => assert_eq!(status.signal_level, 9),
+        reply => panic!("{:?}", reply),
+    }
+    match process("SCAN LOW", &mut radio_tuner) {
+        Reply::Scan { stations, count } => assert_eq!(&stations[..count], &[89.9, 95.1, 101.3]),
+        reply => panic!("{:?}", reply),
+    }
+    assert_eq!(process("MUTE L", &mut radio_tuner), Reply::Ok);
+    assert_eq!(format!("{}", process("TUNE", &mut radio_tuner)), "ERR MISSING");
+}
+
+#[test]
+fn test_monitor_station_loss() {
+    use tea5767::monitor::{MonitorConfig, MonitorEvent, StationMonitor};
+
+    const FADED: [Station; 2] = [STATIONS[0], STATIONS[2]];
+
+    let radio_tuner = TEA5767::new(setup(&STATIONS), 95.1, BandLimits::EuropeUS,
+                                   SoundMode::Stereo).unwrap();
+    let (mut sim, mut radio_tuner) = radio_tuner.detach();
+    let mut monitor = StationMonitor::new(MonitorConfig::default());
+    let mut delay = NoDelay::default();
+
+    let mut check = |sim: &mut SimulatedTuner, now_ms| {
+        radio_tuner.with_bus(sim, |tuner| monitor.check(tuner, now_ms, &mut delay)).unwrap()
+    };
+
+    assert_eq!(check(&mut sim, 0), None);
+    sim.set_stations(&FADED);
+    assert_eq!(check(&mut sim, 100), Some(MonitorEvent::SignalWeak(95.1)));
+    assert_eq!(check(&mut sim, 2000), None);
+    // strongest station meeting criteria, 89.9 MHz is too weak
+    assert_eq!(check(&mut sim, 3100),
+               Some(MonitorEvent::Retuned { lost: 95.1, frequency: 101.3 }));
+    assert_eq!(check(&mut sim, 3200), None);
+    let frequency = radio_tuner.with_bus(&mut sim, |tuner| tuner.get_frequency()).unwrap();
+    assert_eq!(frequency, 101.3);
+}
+
+#[test]
+fn test_monitor_signal_recovered() {
+    use tea5767::monitor::{MonitorConfig, MonitorEvent, StationMonitor};
+
+    const FADED: [Station; 2] = [STATIONS[0], STATIONS[2]];
+
+    let radio_tuner = TEA5767::new(setup(&STATIONS), 95.1, BandLimits::EuropeUS,
+                                   SoundMode::Stereo).unwrap();
+    let (mut sim, mut radio_tuner) = radio_tuner.detach();
+    let mut monitor = StationMonitor::new(MonitorConfig::default());
+    let mut delay = NoDelay::default();
+
+    sim.set_stations(&FADED);
+    let event = radio_tuner.with_bus(&mut sim, |tuner| monitor.check(tuner, 0, &mut delay));
+    assert_eq!(event.unwrap(), Some(MonitorEvent::SignalWeak(95.1)));
+    sim.set_stations(&STATIONS);
+    let event = radio_tuner.with_bus(&mut sim, |tuner| monitor.check(tuner, 1000, &mut delay));
+    assert_eq!(event.unwrap(), Some(MonitorEvent::SignalRecovered(95.1)));
+    assert!(!monitor.is_weak());
+    // nothing searched
+    assert_eq!(delay.elapsed_ms, 0);
+}
+
+#[test]
+fn test_af_follow_stronger() {
+    use tea5767::af::{AfConfig, AfEvent, AfFollower};
+
+    const PROGRAMME: [f32; 3] = [89.9, 95.1, 101.3];
+
+    let mut radio_tuner = TEA5767::new(setup(&STATIONS), 95.1, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    let mut follower = AfFollower::new(&PROGRAMME, AfConfig::default());
+    let mut delay = NoDelay::default();
+
+    // 89.9 MHz weaker, audio back on 95.1 MHz
+    assert_eq!(follower.check(&mut radio_tuner, 0, &mut delay).unwrap(),
+               Some(AfEvent::Measured { frequency: 89.9, level: 6 }));
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 95.1);
+    assert_eq!(follower.check(&mut radio_tuner, 1000, &mut delay).unwrap(), None);
+    // 101.3 MHz stronger by more than hysteresis
+    assert_eq!(follower.check(&mut radio_tuner, 5000, &mut delay).unwrap(),
+               Some(AfEvent::Switched { from: 95.1, to: 101.3 }));
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 101.3);
+
+    let sim = radio_tuner.destroy().unwrap();
+    assert_eq!(sim.last_write()[0] & 0x80, 0);
+    assert_eq!(delay.elapsed_ms, 20);
+}
+
+#[test]
+fn test_af_hysteresis() {
+    use tea5767::af::{AfConfig, AfEvent, AfFollower};
+
+    const PROGRAMME: [f32; 2] = [95.1, 101.3];
+
+    let mut radio_tuner = TEA5767::new(setup(&STATIONS), 95.1, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    let config = AfConfig { hysteresis: 5, ..AfConfig::default() };
+    let mut follower = AfFollower::new(&PROGRAMME, config);
+    let mut delay = NoDelay::default();
+
+    assert_eq!(follower.check(&mut radio_tuner, 0, &mut delay).unwrap(),
+               Some(AfEvent::Measured { frequency: 101.3, level: 13 }));
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 95.1);
+
+    // tuned frequency not in programme list
+    radio_tuner.set_frequency(89.9).unwrap();
+    assert_eq!(follower.check(&mut radio_tuner, 10000, &mut delay).unwrap(), None);
+}
+
+#[test]
+fn test_calibrate_crystal() {
+    // offsets between whole IF counts are estimated within the resolution
+    for &offset in [-75.0, 110.0].iter() {
+        let mut sim = setup(&STATIONS);
+        sim.set_crystal_ppm(offset);
+        let mut radio_tuner = TEA5767::new(sim, 95.1, BandLimits::EuropeUS,
+                                           SoundMode::Stereo).unwrap();
+        let mut delay = NoDelay::default();
+        let ppm = radio_tuner.calibrate_crystal(&[89.9, 95.1, 101.3], 10, &mut delay).unwrap();
+        // IF counter resolution is about 40 ppm at the band
+        assert!((ppm.expect("no station measured") - offset).abs() < 40.0, "{:?}", ppm);
+    }
+
+    let mut sim = setup(&STATIONS);
+    sim.set_crystal_ppm(60.0);
+    let mut radio_tuner = TEA5767::new(sim, 95.1, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    let mut delay = NoDelay::default();
+
+    let ppm = radio_tuner.calibrate_crystal(&[89.9, 95.1, 101.3], 10, &mut delay).unwrap();
+    let ppm = ppm.expect("no station measured");
+    assert!((ppm - 60.0).abs() < 20.0, "{}", ppm);
+    assert_eq!(radio_tuner.crystal_ppm(), ppm);
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 95.1);
+
+    // corrected PLL word written for the tuned frequency
+    let sim = radio_tuner.destroy().unwrap();
+    let pll = tea5767::regs::WriteRegisters::from_bytes(sim.last_write()).pll;
+    assert!(pll < frequency_to_pll(95.1));
+}
+
+#[test]
+fn test_calibrate_crystal_below_resolution() {
+    let mut sim = setup(&STATIONS);
+    sim.set_crystal_ppm(15.0);
+    let mut radio_tuner = TEA5767::new(sim, 95.1, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    radio_tuner.set_crystal_ppm(60.0).unwrap();
+    let mut delay = NoDelay::default();
+
+    // offset within one IF count is not measurable
+    let ppm = radio_tuner.calibrate_crystal(&[89.9, 95.1, 101.3], 10, &mut delay).unwrap();
+    assert_eq!(ppm, Some(0.0));
+    assert_eq!(radio_tuner.crystal_ppm(), 0.0);
+}
+
+#[test]
+fn test_calibrate_crystal_no_station() {
+    let mut radio_tuner = TEA5767::new(setup(&STATIONS), 95.1, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    let mut delay = NoDelay::default();
+
+    assert_eq!(radio_tuner.calibrate_crystal(&[92.0, 104.0], 10, &mut delay).unwrap(), None);
+    assert_eq!(radio_tuner.crystal_ppm(), 0.0);
+}
+
+#[test]
+fn test_detect_clock() {
+    for &clock in [CrystalFrequency::Clk13Mhz, CrystalFrequency::Clk6_5MHz,
+        CrystalFrequency::Clk32_768Khz].iter() {
+        let mut sim = setup(&STATIONS);
+        sim.set_clock(clock);
+        let mut radio_tuner = TEA5767::new(sim, 95.1, BandLimits::EuropeUS,
+                                           SoundMode::Stereo).unwrap();
+        let mut delay = NoDelay::default();
+
+        // preset tuning leaves the ready flag clear, lock shows in the IF counter only
+        assert!(!radio_tuner.get_status().unwrap().ready);
+        assert_eq!(radio_tuner.detect_clock(101.3, 10, &mut delay).unwrap(), Some(clock));
+        assert_eq!(radio_tuner.config().crystal_frequency, clock);
+        assert_eq!(radio_tuner.get_frequency().unwrap(), 95.1);
+        assert_eq!(radio_tuner.get_signal_level().unwrap(), 9);
+    }
+}
+
+#[test]
+fn test_detect_clock_bus_error() {
+    use std::cell::RefCell;
+    use tea5767::bus::RefCellDevice;
+
+    let bus = RefCell::new(setup(&STATIONS));
+    let mut radio_tuner = TEA5767::new(RefCellDevice::new(&bus), 95.1, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    let mut delay = NoDelay::default();
+
+    // second clock setting fails
+    bus.borrow_mut().pass_next = 2;
+    bus.borrow_mut().fail_next = 1;
+    assert!(radio_tuner.detect_clock(101.3, 10, &mut delay).is_err());
+    assert_eq!(radio_tuner.config().crystal_frequency, CrystalFrequency::Clk32_768Khz);
+    assert_eq!(radio_tuner.config().mute, MuteChannel::None);
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 95.1);
+}
+
+#[test]
+fn test_detect_clock_no_station() {
+    let mut sim = setup(&STATIONS);
+    sim.set_clock(CrystalFrequency::Clk13Mhz);
+    let mut radio_tuner = TEA5767::new(sim, 95.1, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    let mut delay = NoDelay::default();
+
+    assert_eq!(radio_tuner.detect_clock(92.0, 10, &mut delay).unwrap(), None);
+    assert_eq!(radio_tuner.config().crystal_frequency, CrystalFrequency::Clk32_768Khz);
+}
+
+#[test]
+fn test_self_test() {
+    let mut radio_tuner = TEA5767::new(setup(&STATIONS), 95.1, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    radio_tuner.mute_left().unwrap();
+    let mut delay = NoDelay::default();
+
+    let report = radio_tuner.self_test(&mut delay).unwrap();
+    assert!(report.passed(), "{:?}", report);
+
+    // state restored
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 95.1);
+    assert_eq!(radio_tuner.config().mute, MuteChannel::Left);
+    let sim = radio_tuner.destroy().unwrap();
+    let writes: Vec<_> = sim.writes.iter()
+        .map(|&bytes| tea5767::regs::WriteRegisters::from_bytes(bytes))
+        .collect();
+    let registers = writes.last().unwrap();
+    assert!(registers.mute_left && !registers.standby && !registers.search_mode);
+
+    // tuning away from the station is muted, mute and standby bits have been written
+    let tuned = frequency_to_pll(95.1);
+    assert!(writes.iter()
+        .filter(|registers| registers.pll != tuned)
+        .all(|registers| registers.mute));
+    assert!(writes.iter().any(|registers| registers.pll == tuned && registers.mute
+        && !registers.standby));
+    assert!(writes.iter().any(|registers| registers.pll == tuned && registers.standby));
+}
+
+#[test]
+fn test_self_test_bus_error() {
+    use std::cell::RefCell;
+    use tea5767::bus::RefCellDevice;
+
+    let bus = RefCell::new(setup(&STATIONS));
+    let mut radio_tuner = TEA5767::new(RefCellDevice::new(&bus), 95.1, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    let mut delay = NoDelay::default();
+    let registers = radio_tuner.write_registers();
+
+    let transactions = |bus: &RefCell<SimulatedTuner>| {
+        bus.borrow().writes.len() + bus.borrow().reads
+    };
+    let start = transactions(&bus);
+    radio_tuner.self_test(&mut delay).unwrap();
+    let count = transactions(&bus) - start;
+
+    // every transaction of the self-test fails once
+    for pass in 0..count {
+        bus.borrow_mut().pass_next = pass;
+        bus.borrow_mut().fail_next = 1;
+        assert!(radio_tuner.self_test(&mut delay).is_err(), "{}", pass);
+        assert_eq!(radio_tuner.write_registers(), registers, "{}", pass);
+    }
+}
+
+#[test]
+fn test_self_test_band_limit_search() {
+    let mut radio_tuner = TEA5767::new(setup(&STATIONS), 80.0, BandLimits::Japanese,
+                                       SoundMode::Stereo).unwrap();
+    radio_tuner.set_region(Region::Japan).unwrap();
+    let mut delay = NoDelay::default();
+
+    let report = radio_tuner.self_test(&mut delay).unwrap();
+    assert!(report.band_limit_high && report.band_limit_low, "{:?}", report);
+
+    // searches are muted and start at the band edges beyond the 90 MHz range end
+    let sim = radio_tuner.destroy().unwrap();
+    let searches: Vec<_> = sim.writes.iter()
+        .map(|&bytes| tea5767::regs::WriteRegisters::from_bytes(bytes))
+        .filter(|registers| registers.search_mode)
+        .collect();
+    assert_eq!(searches.len(), 2);
+    assert!(searches.iter().all(|registers| registers.mute));
+    assert_eq!(searches[0].pll, frequency_to_pll(91.0));
+    assert_eq!(searches[1].pll, frequency_to_pll(76.0));
+    let registers = tea5767::regs::WriteRegisters::from_bytes(sim.last_write());
+    assert!(!registers.mute && !registers.search_mode);
+}
+
+#[test]
+fn test_self_test_wrong_clock() {
+    let mut sim = setup(&STATIONS);
+    sim.set_clock(CrystalFrequency::Clk13Mhz);
+    let mut radio_tuner = TEA5767::new(sim, 95.1, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    let mut delay = NoDelay::default();
+
+    let report = radio_tuner.self_test(&mut delay).unwrap();
+    assert!(report.chip_id && !report.if_counter && !report.band_limit_high);
+    assert!(!report.passed());
+}
+
+#[test]
+fn test_retry_transient_failures() {
+    use std::cell::RefCell;
+    use tea5767::bus::RefCellDevice;
+    use tea5767::retry::{RetryDevice, RetryPolicy, RetryStats};
+
+    let bus = RefCell::new(setup(&STATIONS));
+    let policy = RetryPolicy { attempts: 3, backoff_ms: 2 };
+    let device = RetryDevice::new(RefCellDevice::new(&bus), policy)
+        .with_delay(NoDelay::default());
+    let mut radio_tuner = TEA5767::new(device, 88.0, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+
+    bus.borrow_mut().fail_next = 2;
+    assert_eq!(radio_tuner.search_up(SearchAdcLevel::High, 88.0).unwrap(),
+               SearchStatus::Success);
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 101.3);
+    assert_eq!(radio_tuner.retry_stats(), RetryStats { retries: 2, failures: 0 });
+
+    // out of attempts
+    bus.borrow_mut().fail_next = 3;
+    assert_eq!(radio_tuner.get_signal_level(), Err(SimError::Nack));
+    assert_eq!(radio_tuner.retry_stats(), RetryStats { retries: 4, failures: 1 });
+
+    radio_tuner.reset_retry_stats();
+    assert_eq!(radio_tuner.retry_stats(), RetryStats::default());
+}
+
+#[test]
+fn test_retry_classifier() {
+    use std::cell::RefCell;
+    use tea5767::bus::RefCellDevice;
+    use tea5767::retry::{RetryDevice, RetryPolicy, RetryStats};
+
+    let bus = RefCell::new(setup(&STATIONS));
+    let device = RetryDevice::new(RefCellDevice::new(&bus), RetryPolicy::default())
+        .with_classifier(|_error: &SimError| false);
+    let mut radio_tuner = TEA5767::new(device, 95.1, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+
+    // error not to retry returned at once
+    bus.borrow_mut().fail_next = 1;
+    assert_eq!(radio_tuner.set_frequency(101.3), Err(SimError::Nack));
+    assert_eq!(radio_tuner.retry_stats(), RetryStats { retries: 0, failures: 1 });
+}
+
+#[test]
+fn test_trace_and_replay() {
+    use tea5767::trace::{ReplayDevice, ReplayError, TraceBuffer, TraceDevice, TraceRegisters};
+
+    let mut now = 0;
+    let clock = move || {
+        now += 10;
+        now
+    };
+    let device = TraceDevice::new(setup(&STATIONS), clock, TraceBuffer::<64>::new());
+    let mut radio_tuner = TEA5767::new(device, 88.0, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    radio_tuner.search_up(SearchAdcLevel::High, 88.0).unwrap();
+    let level = radio_tuner.get_signal_level().unwrap();
+
+    let trace = radio_tuner.trace();
+    assert_eq!(trace.dropped(), 0);
+    let first = trace.iter().next().unwrap();
+    assert_eq!(first.timestamp, 10);
+    match first.decode() {
+        Some(TraceRegisters::Write(registers)) => {
+            assert_eq!(registers.pll, frequency_to_pll(88.0));
+        }
+        registers => panic!("{:?}", registers),
+    }
+
+    // same calls reproduce the session from the trace
+    let mut replayed = TEA5767::new(ReplayDevice::new(trace.iter()), 88.0, BandLimits::EuropeUS,
+                                    SoundMode::Stereo).unwrap();
+    assert_eq!(replayed.search_up(SearchAdcLevel::High, 88.0).unwrap(), SearchStatus::Success);
+    assert_eq!(replayed.get_signal_level().unwrap(), level);
+    assert_eq!(replayed.get_frequency(), Err(ReplayError::End));
+
+    // driver diverging from the trace
+    let mut replayed = TEA5767::new(ReplayDevice::new(trace.iter()), 88.0, BandLimits::EuropeUS,
+                                    SoundMode::Stereo).unwrap();
+    assert_eq!(replayed.set_frequency(95.1), Err(ReplayError::Mismatch(1)));
+}
+
+#[test]
+fn test_service_events() {
+    use tea5767::events::{EventQueue, TunerEvent};
+
+    let mut radio_tuner = TEA5767::new(setup(&STATIONS), 95.1, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    let mut queue = EventQueue::<8>::new();
+
+    radio_tuner.service(&mut queue).unwrap();
+    assert_eq!(queue.pop(), Some(TunerEvent::StandbyChanged(false)));
+    assert_eq!(queue.pop(), Some(TunerEvent::Tuned(95.1)));
+    assert_eq!(queue.pop(), Some(TunerEvent::StereoChanged(SoundMode::Stereo)));
+    assert_eq!(queue.pop(), Some(TunerEvent::LevelChanged(9)));
+    assert!(queue.is_empty());
+    radio_tuner.service(&mut queue).unwrap();
+    assert!(queue.is_empty());
+
+    radio_tuner.search_down(SearchAdcLevel::Low, 89.5).unwrap();
+    radio_tuner.service(&mut queue).unwrap();
+    let frequency = radio_tuner.get_frequency().unwrap();
+    assert_eq!(queue.pop(), Some(TunerEvent::SearchComplete(SearchStatus::Failure, frequency)));
+    assert_eq!(queue.pop(), Some(TunerEvent::Tuned(frequency)));
+    assert_eq!(queue.pop(), Some(TunerEvent::StereoChanged(SoundMode::Mono)));
+    assert_eq!(queue.pop(), Some(TunerEvent::LevelChanged(NOISE_LEVEL)));
+    assert_eq!(queue.pop(), Some(TunerEvent::BandLimitReached));
+    assert!(queue.is_empty());
+
+    radio_tuner.set_standby().unwrap();
+    radio_tuner.service(&mut queue).unwrap();
+    assert_eq!(queue.pop(), Some(TunerEvent::StandbyChanged(true)));
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn test_service_level_deadband() {
+    use tea5767::events::TunerEvent;
+
+    const STRONGER: [Station; 1] = [Station { frequency: 95.1, level: 10, stereo: true }];
+    const STRONGEST: [Station; 1] = [Station { frequency: 95.1, level: 12, stereo: true }];
+
+    let radio_tuner = TEA5767::new(setup(&STATIONS), 95.1, BandLimits::EuropeUS,
+                                   SoundMode::Stereo).unwrap();
+    let (mut sim, mut radio_tuner) = radio_tuner.detach();
+    let mut events = Vec::new();
+    let mut service = |sim: &mut SimulatedTuner, events: &mut Vec<TunerEvent>| {
+        radio_tuner.with_bus(sim, |tuner| tuner.service(&mut |event| events.push(event))).unwrap()
+    };
+
+    service(&mut sim, &mut events);
+    events.clear();
+    sim.set_stations(&STRONGER);
+    service(&mut sim, &mut events);
+    assert!(events.is_empty());
+    sim.set_stations(&STRONGEST);
+    service(&mut sim, &mut events);
+    assert_eq!(events, [TunerEvent::LevelChanged(12)]);
+}
+
+#[test]
+fn test_listening_profile() {
+    use tea5767::profile::ListeningProfile;
+    use tea5767::regs::WriteRegisters;
+
+    let mut radio_tuner = TEA5767::new(setup(&STATIONS), 95.1, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    radio_tuner.set_listening_profile(ListeningProfile::Speech).unwrap();
+    assert_eq!(radio_tuner.profile_settings(), ListeningProfile::Speech.settings());
+    // profiles without sound mode restore the sound mode of the user
+    radio_tuner.set_listening_profile(ListeningProfile::WeakSignal).unwrap();
+    assert_eq!(radio_tuner.config().sound_mode, SoundMode::Stereo);
+    // mono chosen while a profile forces mono is kept
+    radio_tuner.set_listening_profile(ListeningProfile::Speech).unwrap();
+    radio_tuner.set_mono().unwrap();
+    radio_tuner.set_listening_profile(ListeningProfile::HiFi).unwrap();
+    assert_eq!(radio_tuner.config().sound_mode, SoundMode::Mono);
+
+    let sim = radio_tuner.destroy().unwrap();
+    assert_eq!(sim.writes.len(), 6);
+    let registers = WriteRegisters::from_bytes(sim.last_write());
+    assert!(!registers.high_cut_control);
+    assert!(!registers.stereo_noise_canceling);
+    assert!(!registers.soft_mute);
+    assert!(registers.mono);
+}
+
+#[test]
+fn test_adaptive_profile() {
+    use tea5767::profile::{AdaptiveConfig, AdaptiveProfile, ListeningProfile, ProfileSettings};
+
+    let mut radio_tuner = TEA5767::new(setup(&STATIONS), 101.3, BandLimits::EuropeUS,
+                                       SoundMode::Mono).unwrap();
+    let mut adaptive = AdaptiveProfile::new(AdaptiveConfig::default());
+    let hifi = ProfileSettings { sound_mode: Some(SoundMode::Mono),
+        ..ListeningProfile::HiFi.settings() };
+
+    assert_eq!(adaptive.check(&mut radio_tuner), Ok(Some(ListeningProfile::HiFi)));
+    assert_eq!(radio_tuner.profile_settings(), hifi);
+    assert_eq!(adaptive.check(&mut radio_tuner), Ok(None));
+
+    radio_tuner.set_frequency(92.0).unwrap();
+    assert_eq!(adaptive.check(&mut radio_tuner), Ok(Some(ListeningProfile::WeakSignal)));
+    assert_eq!(adaptive.is_weak(), Some(true));
+
+    // level 6 is between the thresholds
+    radio_tuner.set_frequency(89.9).unwrap();
+    assert_eq!(adaptive.check(&mut radio_tuner), Ok(None));
+    radio_tuner.set_frequency(95.1).unwrap();
+    assert_eq!(adaptive.check(&mut radio_tuner), Ok(Some(ListeningProfile::HiFi)));
+    assert_eq!(radio_tuner.profile_settings(), hifi);
+
+    // speech in mono for weak reception, stereo of the user restored for strong reception
+    let mut radio_tuner = TEA5767::new(setup(&STATIONS), 92.0, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    let config = AdaptiveConfig { weak: ListeningProfile::Speech, ..AdaptiveConfig::default() };
+    let mut adaptive = AdaptiveProfile::new(config);
+    assert_eq!(adaptive.check(&mut radio_tuner), Ok(Some(ListeningProfile::Speech)));
+    assert_eq!(radio_tuner.config().sound_mode, SoundMode::Mono);
+    radio_tuner.set_frequency(101.3).unwrap();
+    assert_eq!(adaptive.check(&mut radio_tuner), Ok(Some(ListeningProfile::HiFi)));
+    assert_eq!(radio_tuner.config().sound_mode, SoundMode::Stereo);
+
+    // strong level below weak level is raised
+    let config = AdaptiveConfig { weak_level: 8, strong_level: 3, ..AdaptiveConfig::default() };
+    assert_eq!(AdaptiveProfile::new(config).config().strong_level, 8);
+}
+
+#[test]
+fn test_tune_channel() {
+    use tea5767::channel::{ChannelError, ChannelNumbering, TuneChannelError};
+
+    let mut radio_tuner = TEA5767::new(setup(&STATIONS), 95.1, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    radio_tuner.set_region(Region::USA).unwrap();
+    assert_eq!(radio_tuner.current_channel(), Some(36));
+
+    radio_tuner.set_channel_numbering(ChannelNumbering::Fcc);
+    assert_eq!(radio_tuner.current_channel(), Some(236));
+    radio_tuner.tune_channel(266).unwrap();
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 101.1);
+    assert_eq!(radio_tuner.current_channel(), Some(266));
+
+    assert_eq!(radio_tuner.tune_channel(301),
+               Err(TuneChannelError::Channel(ChannelError::OutOfBand)));
+    assert_eq!(radio_tuner.current_channel(), Some(266));
+
+    // Europe grid 0.1 MHz from 87.5 MHz
+    radio_tuner.set_region(Region::Europe).unwrap();
+    radio_tuner.set_channel_numbering(ChannelNumbering::Grid);
+    radio_tuner.tune_channel(0).unwrap();
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 87.5);
+    assert_eq!(radio_tuner.tune_channel(206),
+               Err(TuneChannelError::Channel(ChannelError::OutOfBand)));
+}
+
+#[test]
+fn test_step_wraps() {
+    let mut radio_tuner = TEA5767::new(setup(&STATIONS), 107.9, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    radio_tuner.step(1).unwrap();
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 108.0);
+    radio_tuner.step(2).unwrap();
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 87.6);
+    radio_tuner.step(-2).unwrap();
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 108.0);
+
+    radio_tuner.set_region(Region::USA).unwrap();
+    radio_tuner.step(-3).unwrap();
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 107.3);
+
+    // zero spacing is rejected, stepping stays on the configured grid
+    let config = TunerConfig { channel_spacing: 0.0, ..radio_tuner.config() };
+    assert_eq!(radio_tuner.set_config(&config), Err(ConfigError::Invalid));
+    radio_tuner.step(1).unwrap();
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 107.5);
+}
+
+#[test]
+fn test_step_acceleration_and_mute() {
+    use tea5767::step::{StepConfig, StepControl};
+
+    let mut radio_tuner = TEA5767::new(setup(&STATIONS), 95.1, BandLimits::EuropeUS,
+                                       SoundMode::Stereo).unwrap();
+    let mut control = StepControl::new(StepConfig::default());
+
+    control.step(&mut radio_tuner, 1, 0).unwrap();
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 95.2);
+    assert!(!radio_tuner.write_registers().mute);
+
+    control.step(&mut radio_tuner, 1, 50).unwrap();
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 95.7);
+    assert!(radio_tuner.write_registers().mute);
+
+    assert_eq!(control.poll(&mut radio_tuner, 100), Ok(false));
+    assert_eq!(control.poll(&mut radio_tuner, 150), Ok(true));
+    assert!(!radio_tuner.write_registers().mute);
+    assert!(!control.is_muted());
 
-}
\ No newline at end of file
+    control.step(&mut radio_tuner, -1, 400).unwrap();
+    assert_eq!(radio_tuner.get_frequency().unwrap(), 95.6);
+    assert!(!radio_tuner.write_registers().mute);
+}
@@ -0,0 +1,60 @@
+#![cfg(feature = "serde")]
+
+use tea5767::defs::*;
+
+#[test]
+fn test_serde_status_layout() {
+    let status = TunerStatus {
+        frequency: 101.1,
+        sound_mode: SoundMode::Stereo,
+        signal_level: 12,
+        ready: true,
+        band_limit: false,
+    };
+    let mut buffer = [0_u8; 32];
+    let bytes = postcard::to_slice(&status, &mut buffer).unwrap();
+    // f32 little endian, variant index, u8, bools
+    assert_eq!(bytes, &[0x33, 0x33, 0xCA, 0x42, 0, 12, 1, 0]);
+    assert_eq!(postcard::from_bytes::<TunerStatus>(bytes).unwrap(), status);
+}
+
+#[test]
+fn test_serde_enum_layout() {
+    let mut buffer = [0_u8; 8];
+    assert_eq!(postcard::to_slice(&BandLimits::EuropeUS, &mut buffer).unwrap(), &[1]);
+    assert_eq!(postcard::to_slice(&SoundMode::Mono, &mut buffer).unwrap(), &[1]);
+    assert_eq!(postcard::to_slice(&MuteChannel::None, &mut buffer).unwrap(), &[3]);
+    assert_eq!(postcard::to_slice(&CrystalFrequency::Clk6_5MHz, &mut buffer).unwrap(), &[2]);
+    assert_eq!(postcard::to_slice(&DeemphasisTime::Dtc50, &mut buffer).unwrap(), &[1]);
+    assert_eq!(postcard::to_slice(&SearchAdcLevel::High, &mut buffer).unwrap(), &[2]);
+}
+
+#[test]
+fn test_serde_config() {
+    let profile = Region::Japan.profile();
+    let config = TunerConfig {
+        band_limits: profile.band_limits,
+        frequency_range: profile.frequency_range,
+        deemphasis_time: profile.deemphasis_time,
+        mute: MuteChannel::Left,
+        ..TunerConfig::default()
+    };
+    let mut buffer = [0_u8; 64];
+    let bytes = postcard::to_slice(&config, &mut buffer).unwrap();
+    assert_eq!(postcard::from_bytes::<TunerConfig>(bytes).unwrap(), config);
+}
+
+#[cfg(feature = "presets")]
+#[test]
+fn test_serde_presets() {
+    use tea5767::presets::Presets;
+
+    let mut presets = Presets::new(TunerConfig::default(), 95.1);
+    assert!(presets.set(0, 101.3));
+    assert!(presets.set(4, 89.9));
+    let mut buffer = [0_u8; 128];
+    let bytes = postcard::to_slice(&presets, &mut buffer).unwrap();
+    let restored = postcard::from_bytes::<Presets>(bytes).unwrap();
+    assert_eq!(restored, presets);
+    assert_eq!(restored.get(4), Some(89.9));
+}